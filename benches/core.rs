@@ -0,0 +1,110 @@
+// compare against a saved run before merging changes to move generation:
+//     cargo bench --bench core -- --save-baseline main     (on the base branch)
+//     cargo bench --bench core -- --baseline main          (on the change)
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use rust_chess_engine::bit_board::BitBoard;
+use rust_chess_engine::perft::Perft;
+
+
+// from https://www.chessprogramming.org/Perft_Results: opening, a busy middlegame with every special
+// move, a sparse endgame, promotions and checks, and a quiet symmetrical middlegame
+const POSITIONS : [(&str, &str) ; 5] = [
+    ("start", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+    ("endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+    ("promotions", "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1"),
+    ("middlegame", "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10"),
+];
+
+fn boards() -> Vec<(&'static str, BitBoard)> {
+    POSITIONS.iter().map(|(name, fen)| (*name, BitBoard::fen_to_bitboard(fen).unwrap())).collect()
+}
+
+fn bench_generate_moves(c : &mut Criterion) {
+    let mut group = c.benchmark_group("generate_moves");
+    for (name, board) in boards() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &board, |b, board| {
+            b.iter(|| black_box(board).generate_moves(board.player))
+        });
+    }
+    group.finish();
+}
+
+fn bench_generate_attack_maps(c : &mut Criterion) {
+    let mut group = c.benchmark_group("generate_attack_maps");
+    for (name, mut board) in boards() {
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                let turn = board.player;
+                black_box(&mut board).generate_attack_maps(turn);
+                board.generate_attack_maps(!turn);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_king_in_check(c : &mut Criterion) {
+    let mut group = c.benchmark_group("king_in_check");
+    for (name, board) in boards() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &board, |b, board| {
+            b.iter(|| black_box(board).king_in_check(board.player))
+        });
+    }
+    group.finish();
+}
+
+// every pseudo-legal move of the position applied and taken back
+fn bench_apply_unapply(c : &mut Criterion) {
+    let mut group = c.benchmark_group("apply_unapply");
+    for (name, mut board) in boards() {
+        let turn = board.player;
+        let moves = board.generate_moves(turn);
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                for &mov in moves.iter() {
+                    let result = black_box(&mut board).apply_move(turn, mov);
+                    board.unapply_move(turn, mov, result);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_fen_parsing(c : &mut Criterion) {
+    let mut group = c.benchmark_group("fen_to_bitboard");
+    for (name, fen) in POSITIONS {
+        group.bench_with_input(BenchmarkId::from_parameter(name), fen, |b, fen| {
+            b.iter(|| BitBoard::fen_to_bitboard(black_box(fen)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_perft(c : &mut Criterion) {
+    let mut group = c.benchmark_group("perft_3");
+    group.sample_size(20);
+    let perft = Perft::new();
+    for (name, board) in boards() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &board, |b, board| {
+            b.iter(|| perft.count(black_box(board), 3))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_generate_moves,
+    bench_generate_attack_maps,
+    bench_king_in_check,
+    bench_apply_unapply,
+    bench_fen_parsing,
+    bench_perft,
+);
+criterion_main!(benches);
@@ -1,186 +1,211 @@
-
-use lazy_static::lazy_static;
-
-lazy_static! {
-    pub static ref STRAIGHT_RAYS: [[u64; 65]; 4] = initialize_straight_rays();
-    pub static ref DIAGONAL_RAYS: [[u64; 65]; 4] = initialize_diagonal_rays();
-    pub static ref KNIGHT_JUMPS: [u64; 65] = initialize_knight_jumps();
-    pub static ref KING_ATTACKS: [u64; 65] = initialize_king_attacks();
-    pub static ref PAWN_PUSH: [[u64; 65]; 2] = initialize_pawn_push();
-    pub static ref PAWN_CAPTURES: [[u64; 65]; 2] = initialize_pawn_captures();
+// every table has a 65th entry left empty, so a ray can be indexed with get_lsb of an empty set
+pub static STRAIGHT_RAYS: [[u64; 65]; 4] = STRAIGHT;
+pub static DIAGONAL_RAYS: [[u64; 65]; 4] = DIAGONAL;
+pub static KNIGHT_JUMPS: [u64; 65] = initialize_knight_jumps();
+pub static KING_ATTACKS: [u64; 65] = initialize_king_attacks();
+pub static PAWN_PUSH: [[u64; 65]; 2] = initialize_pawn_push();
+pub static PAWN_CAPTURES: [[u64; 65]; 2] = initialize_pawn_captures();
+
+// squares strictly between two squares on a shared rank, file or diagonal, empty otherwise
+pub static BETWEEN: [[u64; 64]; 64] = initialize_between();
+// the whole rank, file or diagonal through two squares, empty when they share none
+pub static LINE: [[u64; 64]; 64] = initialize_line();
+
+
+// north, east, south, west; direction d and (d + 2) % 4 point opposite ways
+const STRAIGHT_OFFSETS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+// north-east, south-east, south-west, north-west
+const DIAGONAL_OFFSETS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (1 , 2),
+    (1 , -2),
+    (-1 , 2),
+    (-1 , -2),
+    (2 , 1),
+    (2 , -1),
+    (-2 , 1),
+    (-2 , -1)
+];
+
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (1 , 1) , (1 , 0) , (1 , -1),
+    (-1 , 1) , (-1 , 0) , (-1 , -1),
+    (0 , 1) , (0 , -1)
+];
+
+
+const fn on_board(x : i32 , y : i32) -> bool {
+    x >= 0 && x < 8 && y >= 0 && y < 8
 }
 
+// every square from `index` to the edge, `index` itself excluded
+const fn ray(index : usize , (h_offset , v_offset) : (i32, i32)) -> u64 {
+    let mut ray = 0u64;
+    let mut x = (index % 8) as i32 + h_offset;
+    let mut y = (index / 8) as i32 + v_offset;
 
-fn initialize_straight_rays() -> [[u64; 65]; 4]  {
-    let mut rays = [[0u64 ; 65] ; 4];
-
-    let straight_rays_direction_and_limit = [
-        (0 , 7) , (1 , 7) , (2 , 0) , (3 , 0)
-    ];
+    while on_board(x, y) {
+        ray |= 1u64 << (y * 8 + x);
+        x += h_offset;
+        y += v_offset;
+    }
 
-    for (direction , limit) in straight_rays_direction_and_limit.iter() {
-        let offset = if *direction == 0 || *direction == 2 {8} else {1};
-        let offset = offset * if *direction == 0 || *direction == 1 {1} else {-1};
+    ray
+}
 
-        let calculate_squares = |pos:usize| -> usize {
-            if *direction == 0 || *direction== 2 {
-                pos / 8
-            } else {
-                pos % 8
-            }
-        };
-
-        for i in 0..64 {
-            let mut current_position = i;
-
-            let mut squares_to_cover = (calculate_squares(current_position) as i32 - *limit as i32).abs() as usize;
-
-            let mut ray = 0u64;
-            
-            while squares_to_cover > 0 {
-                if offset > 0 {
-                    current_position += offset as usize;
-                } else {
-                    current_position -= (-offset) as usize;
-                }
-                ray |= 1u64 << current_position;
-                squares_to_cover -= 1;
-            }
+const fn initialize_rays(offsets : [(i32, i32); 4]) -> [[u64; 65]; 4] {
+    let mut rays = [[0u64 ; 65] ; 4];
 
-            rays[*direction as usize][i] = ray;
+    let mut direction = 0;
+    while direction < 4 {
+        let mut i = 0;
+        while i < 64 {
+            rays[direction][i] = ray(i, offsets[direction]);
+            i += 1;
         }
+        direction += 1;
     }
 
     rays
 }
 
-fn initialize_diagonal_rays() -> [[u64 ; 65] ; 4] {
-    let mut rays = [[0u64 ; 65] ; 4];
-
-
-    let diagonal_rays_and_limits = [
-        (0 , 7 , 7) , (1 , 0 , 7) ,
-        (2 , 0 , 0) , (3 , 7 , 0)
-    ];
+const fn initialize_straight_rays() -> [[u64; 65]; 4] {
+    initialize_rays(STRAIGHT_OFFSETS)
+}
 
-    for (direction , veritcal_limit , horizontal_limit) in diagonal_rays_and_limits.iter() {
-        let horizonal_offset = if *direction == 0 || *direction == 1 {1} else {-1};
-        let vertical_offset = if *direction == 0 || *direction == 3 {8} else {-8};
+const fn initialize_diagonal_rays() -> [[u64 ; 65] ; 4] {
+    initialize_rays(DIAGONAL_OFFSETS)
+}
 
-        for i in 0..64 {
-            let mut squares_to_cover = std::cmp::min(
-                ((i % 8) as i32 - *horizontal_limit).abs() as usize ,
-                ((i / 8) as i32 - *veritcal_limit).abs() as usize
-            );
 
-            let mut current_position = i;
-            let mut ray = 0u64;
+const fn initialize_jumps(offsets : [(i32, i32); 8]) -> [u64 ; 65] {
+    let mut jumps = [0u64 ; 65];
 
-            while squares_to_cover > 0 {
-                current_position = (current_position as i32) + vertical_offset + horizonal_offset;
+    let mut i = 0;
+    while i < 64 {
+        let x = (i % 8) as i32;
+        let y = (i / 8) as i32;
 
-                ray |= 1u64 << current_position;
-                squares_to_cover -= 1;
+        let mut j = 0;
+        while j < 8 {
+            let (h_offset , v_offset) = offsets[j];
+            if on_board(x + h_offset, y + v_offset) {
+                jumps[i] |= 1u64 << ((y + v_offset) * 8 + x + h_offset);
             }
-
-            rays [*direction as usize][i as usize] = ray;
+            j += 1;
         }
+        i += 1;
     }
 
-    rays
+    jumps
 }
 
+const fn initialize_knight_jumps() -> [u64 ; 65] {
+    initialize_jumps(KNIGHT_OFFSETS)
+}
 
-fn initialize_knight_jumps() -> [u64 ; 65] {
-    let knight_jumps = [
-        (1 , 2),
-        (1 , -2),
-        (-1 , 2),
-        (-1 , -2),
-        (2 , 1),
-        (2 , -1),
-        (-2 , 1),
-        (-2 , -1)
-    ];
-
-    let mut rays = [0u64 ; 65];
-
-    for i in 0..64 {
-        let mut ray = 0u64;
-        for (h_offset , v_offset) in knight_jumps.iter() {
-            let x = i % 8;
-            let y = i / 8;
-
-            if x + h_offset >= 8 || x + h_offset < 0 || y + v_offset < 0 || y + v_offset >= 8 {
-                continue;
-            }
+const fn initialize_king_attacks() -> [u64 ; 65] {
+    initialize_jumps(KING_OFFSETS)
+}
 
-            let new_position : usize = (i + (*h_offset as i32) + (*v_offset as i32) * 8 )as usize;
-            ray |= 1u64 << new_position;
-        }
-        rays[i as usize] = ray;
-    }
 
-    rays
-}
+// filled wherever the capture stays on the board, so the other colour's table gives
+// the pawns attacking a square, back rank included
+const fn initialize_pawn_captures() -> [[u64 ; 65] ; 2] {
+    let mut attacks = [[0u64 ; 65] ; 2];
 
+    let mut i = 0;
+    while i < 64 {
+        if i % 8 != 0 {
+            if i < 56 {
+                attacks[0][i] |= 1u64 << (i + 7);
+            }
+            if i >= 8 {
+                attacks[1][i] |= 1u64 << (i - 9);
+            }
+        }
 
-fn initialize_king_attacks() -> [u64 ; 65] {
-    let mut attacks = [0u64 ; 65];
-    
-    let offsets = [
-        (1 , 1) , (1 , 0) , (1 , -1),
-        (-1 , 1) , (-1 , 0) , (-1 , -1),
-        (0 , 1) , (0 , -1)
-    ];
-
-    for i in 0..64 {
-        let mut ray = 0u64;
-        for (v_offset , h_offset) in offsets.iter() {
-            let x = i % 8;
-            let y = i / 8;
-            
-            if x + h_offset >= 8 || x + h_offset < 0 || y + v_offset < 0 || y + v_offset >= 8 {
-                continue;
+        if i % 8 != 7 {
+            if i < 56 {
+                attacks[0][i] |= 1u64 << (i + 9);
+            }
+            if i >= 8 {
+                attacks[1][i] |= 1u64 << (i - 7);
             }
-            
-            let new_position : usize = (i + (*h_offset as i32) + (*v_offset as i32) * 8 )as usize;
-            ray |= 1u64 << new_position;
         }
-        attacks[i as usize] = ray;
+        i += 1;
     }
 
     attacks
 }
 
+const fn initialize_pawn_push () -> [[u64 ; 65] ; 2] {
+    let mut moves = [[0u64 ; 65] ; 2];
 
+    let mut i = 8;
+    while i < 56 {
+        moves[0][i] = 1u64 << (i + 8);
+        moves[1][i] = 1u64 << (i - 8);
+        i += 1;
+    }
 
-fn initialize_pawn_captures() -> [[u64 ; 65] ; 2] {
-    let mut attacks = [[0u64 ; 65] ; 2];
+    moves
+}
 
-    for i in 8..56 {
-        if i % 8 != 0 {
-            attacks[0][i] |= 1u64 << (i + 7);
-            attacks[1][i] |= 1u64 << (i - 9);
-        }
 
-        if i % 8 != 7 {
-            attacks[0][i] |= 1u64 << (i + 9);
-            attacks[1][i] |= 1u64 << (i - 7);    
+const STRAIGHT: [[u64; 65]; 4] = initialize_straight_rays();
+const DIAGONAL: [[u64; 65]; 4] = initialize_diagonal_rays();
+
+// whether `to` lies on a straight or diagonal ray from `from`, and in which direction
+const fn shared_direction(from : usize , to : usize) -> Option<(bool, usize)> {
+    let mut direction = 0;
+    while direction < 4 {
+        if STRAIGHT[direction][from] & (1u64 << to) != 0 {
+            return Some((true, direction));
         }
+        if DIAGONAL[direction][from] & (1u64 << to) != 0 {
+            return Some((false, direction));
+        }
+        direction += 1;
     }
-    
-    attacks 
+    None
 }
 
-fn initialize_pawn_push () -> [[u64 ; 65] ; 2] {
-    let mut moves = [[0u64 ; 65] ; 2];
+const fn initialize_between() -> [[u64; 64]; 64] {
+    let mut between = [[0u64 ; 64] ; 64];
 
-    for i in 8..56 {
-        moves[0][i] |= 1u64 << (i + 8);
-        moves[1][i] |= 1u64 << (i - 8);
-    }    
+    let mut from = 0;
+    while from < 64 {
+        let mut to = 0;
+        while to < 64 {
+            if let Some((straight, direction)) = shared_direction(from, to) {
+                let rays = if straight { &STRAIGHT } else { &DIAGONAL };
+                between[from][to] = rays[direction][from] & !rays[direction][to] & !(1u64 << to);
+            }
+            to += 1;
+        }
+        from += 1;
+    }
 
-    moves
+    between
 }
 
+const fn initialize_line() -> [[u64; 64]; 64] {
+    let mut line = [[0u64 ; 64] ; 64];
+
+    let mut from = 0;
+    while from < 64 {
+        let mut to = 0;
+        while to < 64 {
+            if let Some((straight, direction)) = shared_direction(from, to) {
+                let rays = if straight { &STRAIGHT } else { &DIAGONAL };
+                line[from][to] = rays[direction][from] | rays[(direction + 2) % 4][from] | (1u64 << from);
+            }
+            to += 1;
+        }
+        from += 1;
+    }
+
+    line
+}
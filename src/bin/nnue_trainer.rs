@@ -0,0 +1,138 @@
+use std::{env, fs, process};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use rust_chess_engine::evaluation::nnue::FeatureSet;
+use rust_chess_engine::evaluation::nnue_training::{load_positions, FloatNetwork, Trainer, TrainerConfig};
+use rust_chess_engine::utils::parse_value;
+
+
+const USAGE : &str = "usage: nnue_trainer --data <file> [--data <file> ...] --output <net>
+    [--validation <file>] [--feature-set halfkp|halfka] [--hidden <n>] [--epochs <n>]
+    [--batch-size <n>] [--learning-rate <f>] [--lambda <f>] [--threads <n>] [--seed <n>]
+    [--checkpoint-dir <dir>] [--checkpoint-every <epochs>] [--resume <checkpoint>]
+
+data files hold one \"<fen> | <score> | <result>\" per line, score in centipawns and result
+(1-0, 1/2-1/2, 0-1 or 1.0, 0.5, 0.0) both from white's point of view";
+
+struct Options {
+    data : Vec<String>,
+    validation : Option<String>,
+    output : String,
+    feature_set : FeatureSet,
+    hidden_size : usize,
+    epochs : u32,
+    seed : u64,
+    checkpoint_dir : Option<String>,
+    checkpoint_every : u32,
+    resume : Option<String>,
+    config : TrainerConfig,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        data : Vec::new(),
+        validation : None,
+        output : String::new(),
+        feature_set : FeatureSet::HalfKP,
+        hidden_size : 256,
+        epochs : 10,
+        seed : 0,
+        checkpoint_dir : None,
+        checkpoint_every : 1,
+        resume : None,
+        config : TrainerConfig::default(),
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--data" => options.data.push(parse_value(&arg, args.next())?),
+            "--validation" => options.validation = Some(parse_value(&arg, args.next())?),
+            "--output" => options.output = parse_value(&arg, args.next())?,
+            "--feature-set" => {
+                options.feature_set = match parse_value::<String>(&arg, args.next())?.as_str() {
+                    "halfkp" => FeatureSet::HalfKP,
+                    "halfka" => FeatureSet::HalfKA,
+                    other => return Err(format!("Unknown feature set {}", other)),
+                }
+            }
+            "--hidden" => options.hidden_size = parse_value(&arg, args.next())?,
+            "--epochs" => options.epochs = parse_value(&arg, args.next())?,
+            "--batch-size" => options.config.batch_size = parse_value(&arg, args.next())?,
+            "--learning-rate" => options.config.learning_rate = parse_value(&arg, args.next())?,
+            "--lambda" => options.config.lambda = parse_value(&arg, args.next())?,
+            "--threads" => options.config.threads = parse_value(&arg, args.next())?,
+            "--seed" => options.seed = parse_value(&arg, args.next())?,
+            "--checkpoint-dir" => options.checkpoint_dir = Some(parse_value(&arg, args.next())?),
+            "--checkpoint-every" => options.checkpoint_every = parse_value(&arg, args.next())?,
+            "--resume" => options.resume = Some(parse_value(&arg, args.next())?),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+
+    if options.data.is_empty() || options.output.is_empty() {
+        return Err("--data and --output are required".to_string());
+    }
+    Ok(options)
+}
+
+fn run(options : Options) -> Result<(), String> {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+
+    let mut trainer = match &options.resume {
+        Some(path) => Trainer::load_checkpoint(path, options.config)?,
+        None => Trainer::new(FloatNetwork::new(options.feature_set, options.hidden_size, &mut rng), options.config),
+    };
+    // a resumed network decides the feature set
+    let feature_set = trainer.network.feature_set;
+
+    let mut positions = Vec::new();
+    for path in &options.data {
+        positions.extend(load_positions(path, feature_set)?);
+    }
+    let validation = match &options.validation {
+        Some(path) => load_positions(path, feature_set)?,
+        None => Vec::new(),
+    };
+    println!("loaded {} training and {} validation positions", positions.len(), validation.len());
+
+    if let Some(dir) = &options.checkpoint_dir {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir, e))?;
+    }
+
+    while trainer.epoch < options.epochs {
+        let loss = trainer.train_epoch(&positions, &mut rng);
+        if validation.is_empty() {
+            println!("epoch {:>4}  loss {:.6}", trainer.epoch, loss);
+        } else {
+            println!("epoch {:>4}  loss {:.6}  validation {:.6}", trainer.epoch, loss, trainer.loss(&validation));
+        }
+
+        if let Some(dir) = &options.checkpoint_dir {
+            if trainer.epoch % options.checkpoint_every.max(1) == 0 {
+                trainer.save_checkpoint(&format!("{}/epoch-{}.ckpt", dir, trainer.epoch))?;
+                trainer.network.quantize().save(&format!("{}/epoch-{}.nnue", dir, trainer.epoch))?;
+            }
+        }
+    }
+
+    trainer.network.quantize().save(&options.output)?;
+    println!("wrote {}", options.output);
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(options) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
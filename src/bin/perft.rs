@@ -0,0 +1,152 @@
+use std::{env, fs, process, time::Instant};
+
+use rust_chess_engine::bit_board::BitBoard;
+use rust_chess_engine::perft::{Perft, PerftHashTable};
+use rust_chess_engine::utils::{move_to_uci, parse_value};
+
+
+const USAGE : &str = "usage: perft [--fen <fen>] --depth <n> [--threads <n>] [--hash <mb>] [--no-bulk] [--chess960]
+       perft --epd <file> [--depth <n>] [--threads <n>] [--hash <mb>] [--no-bulk]
+
+with a FEN (the starting position by default) prints the leaf count below every legal move;
+an EPD suite holds one position per line followed by expected counts as \";D1 20 ;D2 400\",
+checked up to --depth when given";
+
+const STARTING_FEN : &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+struct Options {
+    fen : String,
+    epd : Option<String>,
+    depth : Option<usize>,
+    chess960 : bool,
+    perft : Perft,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        fen : STARTING_FEN.to_string(),
+        epd : None,
+        depth : None,
+        chess960 : false,
+        perft : Perft::new(),
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fen" => options.fen = parse_value(&arg, args.next())?,
+            "--epd" => options.epd = Some(parse_value(&arg, args.next())?),
+            "--depth" => options.depth = Some(parse_value(&arg, args.next())?),
+            "--threads" => options.perft.threads = parse_value(&arg, args.next())?,
+            "--hash" => options.perft.hash = Some(PerftHashTable::new(parse_value(&arg, args.next())?)),
+            "--no-bulk" => options.perft.bulk = false,
+            "--chess960" => options.chess960 = true,
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+
+    if options.epd.is_none() && options.depth.is_none() {
+        return Err("--depth is required".to_string());
+    }
+    Ok(options)
+}
+
+fn nodes_per_second(nodes : u64 , seconds : f64) -> u64 {
+    (nodes as f64 / seconds.max(1e-9)) as u64
+}
+
+fn run_divide(options : &Options , depth : usize) -> Result<(), String> {
+    let board = BitBoard::fen_to_valid_bitboard(&options.fen)?;
+
+    let start = Instant::now();
+    let divide = options.perft.divide(&board, depth);
+    let seconds = start.elapsed().as_secs_f64();
+
+    for (mov, nodes) in &divide {
+        println!("{}: {}", move_to_uci(*mov, options.chess960), nodes);
+    }
+    let nodes : u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+    println!();
+    println!("moves {}", divide.len());
+    println!("nodes {}", nodes);
+    println!("time {:.3}s, {} nps", seconds, nodes_per_second(nodes, seconds));
+    Ok(())
+}
+
+// the FEN and the (depth, count) pairs of one suite line
+fn parse_epd_line(line : &str) -> Result<(String, Vec<(usize, u64)>), String> {
+    let mut fields = line.split(';');
+    let position = fields.next().unwrap_or_default().trim();
+    // EPD records stop after the en passant square
+    let fen = if position.split_whitespace().count() == 4 { format!("{} 0 1", position) } else { position.to_string() };
+
+    let mut expected = Vec::new();
+    for field in fields {
+        let mut parts = field.split_whitespace();
+        let (Some(depth), Some(count)) = (parts.next(), parts.next()) else {
+            return Err(format!("Invalid expected count \"{}\"", field.trim()));
+        };
+        let depth = depth.strip_prefix('D').and_then(|depth| depth.parse().ok())
+            .ok_or_else(|| format!("Invalid depth \"{}\"", depth))?;
+        let count = count.parse().map_err(|_| format!("Invalid count \"{}\"", count))?;
+        expected.push((depth, count));
+    }
+    Ok((fen, expected))
+}
+
+fn run_epd(options : &Options , path : &str) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    let (mut failures, mut total_nodes) = (0, 0);
+    let start = Instant::now();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (fen, expected) = parse_epd_line(line).map_err(|e| format!("{} line {}: {}", path, number + 1, e))?;
+        let board = BitBoard::fen_to_valid_bitboard(&fen).map_err(|e| format!("{} line {}: {}", path, number + 1, e))?;
+
+        for (depth, count) in expected {
+            if options.depth.is_some_and(|max_depth| depth > max_depth) {
+                continue;
+            }
+            let position_start = Instant::now();
+            let nodes = options.perft.count(&board, depth);
+            let seconds = position_start.elapsed().as_secs_f64();
+            total_nodes += nodes;
+
+            let verdict = if nodes == count { "ok" } else { failures += 1; "FAILED" };
+            println!("{:<6} D{} {:>12} (expected {:>12}) {:>10} nps  {}",
+                verdict, depth, nodes, count, nodes_per_second(nodes, seconds), fen);
+        }
+    }
+
+    let seconds = start.elapsed().as_secs_f64();
+    println!();
+    println!("nodes {}", total_nodes);
+    println!("time {:.3}s, {} nps", seconds, nodes_per_second(total_nodes, seconds));
+    if failures > 0 {
+        return Err(format!("{} counts did not match", failures));
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let result = match (&options.epd, options.depth) {
+        (Some(path), _) => run_epd(&options, path),
+        (None, Some(depth)) => run_divide(&options, depth),
+        (None, None) => unreachable!(),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
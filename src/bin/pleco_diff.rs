@@ -0,0 +1,70 @@
+use std::{env, process};
+
+use rust_chess_engine::bit_board::BitBoard;
+use rust_chess_engine::pleco_check::{check_random_games, find_divergence};
+use rust_chess_engine::utils::parse_value;
+
+
+const USAGE : &str = "usage: pleco_diff [--games <n>] [--plies <n>] [--depth <n>] [--seed <n>]
+       pleco_diff --fen <fen> --depth <n>
+
+compares our legal moves with pleco's in every position of random games, or below one position;
+with --depth above one the perft counts are compared too, and a difference is followed down to
+the first position whose move lists differ";
+
+struct Options {
+    fen : Option<String>,
+    games : usize,
+    plies : usize,
+    depth : usize,
+    seed : u64,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options { fen : None, games : 100, plies : 200, depth : 1, seed : 0 };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fen" => options.fen = Some(parse_value(&arg, args.next())?),
+            "--games" => options.games = parse_value(&arg, args.next())?,
+            "--plies" => options.plies = parse_value(&arg, args.next())?,
+            "--depth" => options.depth = parse_value(&arg, args.next())?,
+            "--seed" => options.seed = parse_value(&arg, args.next())?,
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+fn run(options : Options) -> Result<(), String> {
+    match &options.fen {
+        Some(fen) => {
+            let mut board = BitBoard::fen_to_valid_bitboard(fen)?;
+            if let Some(mismatch) = find_divergence(&mut board, options.depth)? {
+                return Err(mismatch.to_string());
+            }
+            println!("no difference to depth {}", options.depth);
+        }
+        None => {
+            let checked = check_random_games(options.games, options.plies, options.depth, options.seed)?;
+            println!("{} positions from {} games agree", checked, options.games);
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(options) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
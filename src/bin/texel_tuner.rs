@@ -0,0 +1,114 @@
+use std::{env, fs, process};
+
+use rust_chess_engine::evaluation::params::EvalParams;
+use rust_chess_engine::evaluation::tuning::{fit_k, gradient_descent, load_positions, local_search, mean_error, TunerConfig};
+use rust_chess_engine::utils::parse_value;
+
+
+const USAGE : &str = "usage: texel_tuner --data <file> [--data <file> ...] [--method local|gradient]
+    [--iterations <n>] [--learning-rate <f>] [--k <f>] [--threads <n>]
+    [--params <json>] [--json <file>] [--rust <file>]
+
+data files hold quiet positions as EPD or FEN followed by the game result for white
+(\"1-0\", \"1/2-1/2\", \"0-1\" or [1.0], [0.5], [0.0]); K is fitted unless given, and
+tuning starts from the built-in weights unless --params names a JSON parameter file";
+
+struct Options {
+    data : Vec<String>,
+    gradient : bool,
+    k : Option<f64>,
+    params : Option<String>,
+    json : Option<String>,
+    rust : Option<String>,
+    config : TunerConfig,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        data : Vec::new(),
+        gradient : false,
+        k : None,
+        params : None,
+        json : None,
+        rust : None,
+        config : TunerConfig { k : 1.0, iterations : 100, learning_rate : 1.0, threads : 1 },
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--data" => options.data.push(parse_value(&arg, args.next())?),
+            "--method" => {
+                options.gradient = match parse_value::<String>(&arg, args.next())?.as_str() {
+                    "local" => false,
+                    "gradient" => true,
+                    other => return Err(format!("Unknown method {}", other)),
+                }
+            }
+            "--iterations" => options.config.iterations = parse_value(&arg, args.next())?,
+            "--learning-rate" => options.config.learning_rate = parse_value(&arg, args.next())?,
+            "--k" => options.k = Some(parse_value(&arg, args.next())?),
+            "--threads" => options.config.threads = parse_value(&arg, args.next())?,
+            "--params" => options.params = Some(parse_value(&arg, args.next())?),
+            "--json" => options.json = Some(parse_value(&arg, args.next())?),
+            "--rust" => options.rust = Some(parse_value(&arg, args.next())?),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+
+    if options.data.is_empty() {
+        return Err("--data is required".to_string());
+    }
+    Ok(options)
+}
+
+fn run(mut options : Options) -> Result<(), String> {
+    let mut positions = Vec::new();
+    for path in &options.data {
+        positions.extend(load_positions(path)?);
+    }
+    println!("loaded {} positions", positions.len());
+
+    let start = match &options.params {
+        Some(path) => EvalParams::load(path)?,
+        None => EvalParams::default(),
+    };
+    let threads = options.config.threads;
+    options.config.k = options.k.unwrap_or_else(|| fit_k(&positions, &start, threads));
+    println!("K = {:.4}, starting error {:.8}", options.config.k, mean_error(&positions, &start, options.config.k, threads));
+
+    let mut report = |iteration : usize, error : f64| println!("iteration {:>4}  error {:.8}", iteration, error);
+    let tuned = if options.gradient {
+        gradient_descent(&positions, &start, options.config, &mut report)
+    } else {
+        local_search(&positions, &start, options.config, &mut report)
+    };
+
+    if let Some(path) = &options.json {
+        tuned.save(path)?;
+        println!("wrote {}", path);
+    }
+    if let Some(path) = &options.rust {
+        fs::write(path, tuned.to_rust_source()).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+        println!("wrote {}", path);
+    }
+    if options.json.is_none() && options.rust.is_none() {
+        print!("{}", tuned.to_rust_source());
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(options) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -1,17 +1,20 @@
 use crate::engine::{
-    apply_castle_move, apply_double_pawn_push, apply_enpessant, apply_normal_move, apply_promotion,
-    unapply_castle_move, unapply_double_pawn_push, unapply_normal_move, unapply_promotion,
+    apply_castle_move, apply_double_pawn_push, castle_destinations, apply_enpessant, apply_normal_move, apply_promotion,
+    get_piece_type, unapply_castle_move, unapply_double_pawn_push, unapply_normal_move, unapply_promotion,
     unpply_enpessant,
 };
 use crate::move_generator::{
-    generate_diagonal_moves, generate_king_attacks, generate_king_moves, generate_knight_moves,
+    generate_castle_moves, generate_diagonal_moves, generate_king_attacks, generate_knight_moves,
     generate_pawn_attacks, generate_pawn_moves, generate_straight_moves, iterate_attack_moves,
     iterate_possible_move,
 };
 
+use crate::game_status::GameStatus;
+use crate::move_list::MoveList;
 use crate::piece_set::PieceSet;
-use crate::utils::{ read_move_components,get_lsb, flip_bit, test_bit};
-use crate::attack_maps::{DIAGONAL_RAYS, KING_ATTACKS, KNIGHT_JUMPS, STRAIGHT_RAYS};
+use crate::polyglot::PolyglotBook;
+use crate::utils::{ read_move_components,get_lsb, file_mask, flip_bit, move_to_uci, flip_horizontal, flip_vertical, RANK_1, RANK_8};
+use crate::attack_maps::{BETWEEN, KING_ATTACKS, KNIGHT_JUMPS, LINE, PAWN_CAPTURES};
 use crate::player::Player;
 
 const KNIGHT_PROMOTED: u16 = 1;
@@ -26,7 +29,7 @@ const CASTLE_QUEEN: u16 = 7;
 
 const DOUBLE_PAWN_PUSH: u16 = 8;
 
-#[derive(Eq, Hash, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Debug)]
 pub enum PieceType {
     Pawn,
     Knight,
@@ -36,6 +39,7 @@ pub enum PieceType {
     King,
 }
 
+#[derive(Clone, Copy, Eq, PartialEq)]
 pub enum MoveResult {
     Enpassant(u64, u64),
     Castle(u64, u64),
@@ -44,82 +48,173 @@ pub enum MoveResult {
     DoublePawnPush(u64),
 }
 
-#[derive(Clone, Eq, PartialEq)]
+// what `make_move` needs to take a move back
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct BoardState {
+    pub mov: u16,
+    pub result: MoveResult,
+    pub halfmove_clock: u32,
+    // zobrist key of the position before the move
+    pub key: u64,
+}
+
+// what `gives_check_with` needs to know about a position, worked out once for all of its moves
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CheckInfo {
+    // the side making the moves
+    pub turn: Player,
+    // the square of the king in check, 64 when there is none
+    pub king_index: usize,
+    // for each piece type of `turn`, the squares it would give check from, indexed by `PieceType as usize`
+    pub check_squares: [u64; 6],
+    // pieces of `turn` uncovering a check when they leave the line to the king
+    pub candidates: u64,
+}
+
+// a clone carries the whole move history along, so `unmake_move` keeps working on it
+#[derive(Clone)]
 pub struct BitBoard {
     pub black_set: PieceSet,
     pub white_set: PieceSet,
     pub player: Player,
+    // the piece on every square, kept in step with the piece sets by the apply / unapply functions
+    pub mailbox: [Option<(Player, PieceType)>; 64],
+    // plies since the last capture or pawn move
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    // one entry per `make_move` not yet taken back, oldest first
+    pub history: Vec<BoardState>,
+    // zobrist keys of all the pieces and of the pawns alone, kept up to date by `apply_move` / `unapply_move`
+    pub piece_hash: u64,
+    pub pawn_hash: u64,
+}
+
+// boards are equal when they hold the same position, however each got there
+impl PartialEq for BitBoard {
+    fn eq(&self, other: &BitBoard) -> bool {
+        self.white_set == other.white_set
+            && self.black_set == other.black_set
+            && self.player == other.player
+            && self.mailbox == other.mailbox
+            && self.halfmove_clock == other.halfmove_clock
+            && self.fullmove_number == other.fullmove_number
+    }
+}
+
+impl Eq for BitBoard {}
+
+fn piece_char(player: Player, piece_type: PieceType) -> char {
+    let c = match piece_type {
+        PieceType::Pawn => 'p',
+        PieceType::Knight => 'n',
+        PieceType::Bishop => 'b',
+        PieceType::Rook => 'r',
+        PieceType::Queen => 'q',
+        PieceType::King => 'k',
+    };
+    if player == Player::White { c.to_ascii_uppercase() } else { c }
 }
 
 impl BitBoard {
-    pub fn get_empty_board() -> BitBoard {
-        let board: BitBoard = BitBoard {
-            white_set: PieceSet::get_empty_piece_set(),
-            black_set: PieceSet::get_empty_piece_set(),
-            player: Player::White,
+    fn from_piece_sets(white_set: PieceSet, black_set: PieceSet, player: Player) -> BitBoard {
+        let mut board = BitBoard {
+            white_set,
+            black_set,
+            player,
+            mailbox: [None; 64],
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            history: Vec::new(),
+            piece_hash: 0,
+            pawn_hash: 0,
         };
-        return board;
+        board.refresh_mailbox();
+        board.refresh_keys();
+        board
+    }
+
+    pub fn get_empty_board() -> BitBoard {
+        BitBoard::from_piece_sets(PieceSet::get_empty_piece_set(), PieceSet::get_empty_piece_set(), Player::White)
     }
 
     pub fn get_starting_board() -> BitBoard {
-        return BitBoard {
-            white_set: PieceSet::get_starting_white_set(),
-            black_set: PieceSet::get_starting_black_set(),
-            player: Player::White,
-        };
+        BitBoard::from_piece_sets(PieceSet::get_starting_white_set(), PieceSet::get_starting_black_set(), Player::White)
     }
 
-    pub fn print_board(&self)
-    {
-        let mut view : Vec<char> = vec!['.' ; 64];
+    pub fn piece_at(&self, square: usize) -> Option<(Player, PieceType)> {
+        self.mailbox[square]
+    }
 
-        for i in 0..64 {
-            if (1u64 << i) & self.white_set.pawns != 0 {
-                view[i] = 'P';
-            }
-            else if (1u64 << i) & self.white_set.rooks != 0 {
-                view[i] = 'R';
-            }
-            else if (1u64 << i) & self.white_set.knights != 0 {
-                view[i] = 'N';
-            }
-            else if (1u64 << i) & self.white_set.bishops != 0 {
-                view[i] = 'B';
-            }
-            else if (1u64 << i) & self.white_set.queens != 0 {
-                view[i] = 'Q';
-            }
-            else if (1u64 << i) & self.white_set.kings != 0 {
-                view[i] = 'K';
-            }
-            else if (1u64 << i) & self.black_set.pawns != 0 {
-                view[i] = 'p';
-            }
-            else if (1u64 << i) & self.black_set.rooks != 0 {
-                view[i] = 'r';
-            }
-            else if (1u64 << i) & self.black_set.knights != 0 {
-                view[i] = 'n';
-            }
-            else if (1u64 << i) & self.black_set.bishops != 0 {
-                view[i] = 'b';
-            }
-            else if (1u64 << i) & self.black_set.queens != 0 {
-                view[i] = 'q';
-            }
-            else if (1u64 << i) & self.black_set.kings != 0 {
-                view[i] = 'k';
+    // rebuilds the mailbox from the piece sets, for boards whose bitboards were edited by hand
+    pub fn refresh_mailbox(&mut self) {
+        for (square, entry) in self.mailbox.iter_mut().enumerate() {
+            *entry = get_piece_type(&self.white_set, square).map(|piece_type| (Player::White, piece_type))
+                .or_else(|| get_piece_type(&self.black_set, square).map(|piece_type| (Player::Black, piece_type)));
+        }
+    }
+
+    // the first square where the mailbox and the piece sets disagree
+    pub fn check_mailbox(&self) -> Result<(), String> {
+        for square in 0..64 {
+            let white = get_piece_type(&self.white_set, square);
+            let black = get_piece_type(&self.black_set, square);
+            let expected = match (white, black) {
+                (Some(_), Some(_)) => return Err(format!("Square {} holds both a white and a black piece", square)),
+                (Some(piece_type), None) => Some((Player::White, piece_type)),
+                (None, Some(piece_type)) => Some((Player::Black, piece_type)),
+                (None, None) => None,
+            };
+            if self.mailbox[square] != expected {
+                let describe = |piece: Option<(Player, PieceType)>| piece.map_or('.', |(player, piece_type)| piece_char(player, piece_type));
+                return Err(format!("Mailbox has '{}' on square {} but the bitboards have '{}'",
+                    describe(self.mailbox[square]), square, describe(expected)));
             }
         }
+        Ok(())
+    }
+
+    // the same position with the colours swapped and the board turned upside down,
+    // so every evaluation and move count should come out the same for the new side to move;
+    // the move history is not carried over
+    pub fn mirror_vertical(&self) -> BitBoard {
+        let mut board = BitBoard::from_piece_sets(
+            self.black_set.map_bitboards(flip_vertical),
+            self.white_set.map_bitboards(flip_vertical),
+            !self.player,
+        );
+        board.halfmove_clock = self.halfmove_clock;
+        board.fullmove_number = self.fullmove_number;
+        board
+    }
+
+    // the a-file and h-file swapped; castling rights are dropped since castling is not
+    // left-right symmetric, the king always lands on the c- or g-file
+    pub fn mirror_horizontal(&self) -> BitBoard {
+        let mirror = |pieces: &PieceSet| {
+            let mut mirrored = pieces.map_bitboards(flip_horizontal);
+            mirrored.castle_rooks = 0;
+            mirrored
+        };
+        let mut board = BitBoard::from_piece_sets(mirror(&self.white_set), mirror(&self.black_set), self.player);
+        board.halfmove_clock = self.halfmove_clock;
+        board.fullmove_number = self.fullmove_number;
+        board
+    }
+
+    pub fn print_board(&self)
+    {
+        let view : Vec<char> = self.mailbox.iter()
+            .map(|piece| piece.map_or('.', |(player, piece_type)| piece_char(player, piece_type)))
+            .collect();
 
         for i in (0..8).rev() {
             for j in 0..8 {
                 print!("{} " , view[i * 8 + j]);
             }
-            println!("");
+            println!();
         }
 
-        println!("");
+        println!();
 
     }
 
@@ -190,18 +285,30 @@ impl BitBoard {
             }
         }
         
-        // Parse castling rights
+        // Parse castling rights: KQkq for the outermost rooks (X-FEN), or the rook's file (Shredder-FEN)
         if components.len() > 2 {
             let castling = components[2];
             for c in castling.chars() {
-                match c {
-                    'K' => board.white_set.castle_rooks |= 1u64 << 7,  // H1
-                    'Q' => board.white_set.castle_rooks |= 1u64,       // A1
-                    'k' => board.black_set.castle_rooks |= 1u64 << 63, // H8
-                    'q' => board.black_set.castle_rooks |= 1u64 << 56, // A8
-                    '-' => {}, // No castling rights
-                    _ => {}    // Ignore other characters
+                let (piece_set, back_rank) = if c.is_ascii_uppercase() {
+                    (&mut board.white_set, RANK_1)
+                } else {
+                    (&mut board.black_set, RANK_8)
+                };
+                let king_index = get_lsb(piece_set.kings & back_rank);
+                if king_index == 64 {
+                    continue;
                 }
+                let rooks = piece_set.rooks & back_rank;
+                let kingside_rooks = rooks & (!0u64 << king_index);
+                let queenside_rooks = rooks & ((1u64 << king_index) - 1);
+
+                let rook = match c.to_ascii_lowercase() {
+                    'k' if kingside_rooks != 0 => 1u64 << (63 - kingside_rooks.leading_zeros()),
+                    'q' => queenside_rooks & queenside_rooks.wrapping_neg(),
+                    'a'..='h' => rooks & file_mask(c.to_ascii_lowercase() as usize - 'a' as usize),
+                    _ => 0, // '-' and anything unknown
+                };
+                piece_set.castle_rooks |= rook;
             }
         }
         
@@ -216,30 +323,33 @@ impl BitBoard {
                 let ep_file = en_passant.chars().nth(0).unwrap() as i32 - 'a' as i32;
                 let ep_rank = en_passant.chars().nth(1).unwrap() as i32 - '1' as i32;
                 
-                if ep_file < 0 || ep_file > 7 || ep_rank < 0 || ep_rank > 7 {
+                if !(0..=7).contains(&ep_file) || !(0..=7).contains(&ep_rank) {
                     return Err("Invalid en passant square".to_string());
                 }
                 
-                // Calculate the square of the pawn that made the double push
-                let pawn_rank = if ep_rank == 2 { 3 } else { 4 };
-                let pawn_square = pawn_rank * 8 + ep_file;
-                let pawn_bit = 1u64 << pawn_square;
-                
-                // Mark the pawn as having made a double push
-                if ep_rank == 2 {
-                    board.black_set.double_push_pawns |= pawn_bit;
+                // the target square is kept by the side that pushed, like `apply_double_pawn_push` does;
+                // whether it makes sense is left to `validate`
+                let target = 1u64 << (ep_rank * 8 + ep_file);
+                if ep_rank < 4 {
+                    board.white_set.double_push_pawns |= target;
                 } else {
-                    board.white_set.double_push_pawns |= pawn_bit;
+                    board.black_set.double_push_pawns |= target;
                 }
             }
         }
         
-        let mut turn = Player::White;
-        if components.len() > 4 {
-            turn = if components[4] == "b" {Player::Black} else {Player::White};
-        }
+        let turn = if components[1] == "b" {Player::Black} else {Player::White};
     
         board.player = turn;
+        board.refresh_mailbox();
+        board.refresh_keys();
+
+        if components.len() > 4 {
+            board.halfmove_clock = components[4].parse().map_err(|_| "Invalid halfmove clock".to_string())?;
+        }
+        if components.len() > 5 {
+            board.fullmove_number = components[5].parse().map_err(|_| "Invalid fullmove number".to_string())?;
+        }
 
         board.generate_attack_maps(turn);
         board.generate_attack_maps(!turn);
@@ -257,37 +367,15 @@ impl BitBoard {
             
             for file in 0..8 {
                 let square_index = rank * 8 + file;
-                let bit = 1u64 << square_index;
                 
-                if (self.white_set.occupied | self.black_set.occupied) & bit != 0 {
+                if let Some((player, piece_type)) = self.mailbox[square_index] {
                     // If we had empty squares before this piece, add the count
                     if empty_squares > 0 {
                         fen.push_str(&empty_squares.to_string());
                         empty_squares = 0;
                     }
                     
-                    // Determine which piece is on this square
-                    let piece_char = if self.white_set.occupied & bit != 0 {
-                        // White piece
-                        if self.white_set.pawns & bit != 0 { 'P' }
-                        else if self.white_set.knights & bit != 0 { 'N' }
-                        else if self.white_set.bishops & bit != 0 { 'B' }
-                        else if self.white_set.rooks & bit != 0 { 'R' }
-                        else if self.white_set.queens & bit != 0 { 'Q' }
-                        else if self.white_set.kings & bit != 0 { 'K' }
-                        else { panic!("Invalid piece configuration at square {}", square_index) }
-                    } else {
-                        // Black piece
-                        if self.black_set.pawns & bit != 0 { 'p' }
-                        else if self.black_set.knights & bit != 0 { 'n' }
-                        else if self.black_set.bishops & bit != 0 { 'b' }
-                        else if self.black_set.rooks & bit != 0 { 'r' }
-                        else if self.black_set.queens & bit != 0 { 'q' }
-                        else if self.black_set.kings & bit != 0 { 'k' }
-                        else { panic!("Invalid piece configuration at square {}", square_index) }
-                    };
-                    
-                    fen.push(piece_char);
+                    fen.push(piece_char(player, piece_type));
                 } else {
                     // Empty square
                     empty_squares += 1;
@@ -309,25 +397,33 @@ impl BitBoard {
         fen.push(' ');
         fen.push(if turn { 'b' } else { 'w' });
         
-        // Add castling rights
+        // Add castling rights, KQkq unless an inner rook holds the right (X-FEN)
         fen.push(' ');
         let mut has_castling_rights = false;
-        
-        if self.white_set.castle_rooks & (1u64 << 7) != 0 {
-            fen.push('K');
-            has_castling_rights = true;
-        }
-        if self.white_set.castle_rooks & 1u64 != 0 {
-            fen.push('Q');
-            has_castling_rights = true;
-        }
-        if self.black_set.castle_rooks & (1u64 << 63) != 0 {
-            fen.push('k');
-            has_castling_rights = true;
-        }
-        if self.black_set.castle_rooks & (1u64 << 56) != 0 {
-            fen.push('q');
-            has_castling_rights = true;
+
+        for (pieces, back_rank, white) in [(&self.white_set, RANK_1, true), (&self.black_set, RANK_8, false)] {
+            let king_index = get_lsb(pieces.kings & back_rank);
+            if king_index == 64 {
+                continue;
+            }
+            let rooks = pieces.rooks & back_rank;
+
+            // highest square first, so the kingside right comes before the queenside one
+            let mut castle_rooks = pieces.castle_rooks & back_rank;
+            while castle_rooks != 0 {
+                let index = 63 - castle_rooks.leading_zeros() as usize;
+                flip_bit(&mut castle_rooks, index);
+
+                let kingside = index > king_index;
+                let outer = if kingside { rooks >> index == 1 } else { rooks & ((1u64 << index) - 1) == 0 };
+                let c = match (outer, kingside) {
+                    (true, true) => 'k',
+                    (true, false) => 'q',
+                    _ => (b'a' + (index % 8) as u8) as char,
+                };
+                fen.push(if white { c.to_ascii_uppercase() } else { c });
+                has_castling_rights = true;
+            }
         }
         if !has_castling_rights {
             fen.push('-');
@@ -367,8 +463,7 @@ impl BitBoard {
         }
         
         // Add halfmove clock and fullmove number
-        // Since these aren't tracked in the BitBoard structure, we'll add default values
-        fen.push_str(" 0 1");
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
         
         fen
     }
@@ -376,177 +471,407 @@ impl BitBoard {
 }
 
 impl BitBoard {
-    pub fn generate_moves(&self, turn: Player) -> Vec<u16> {
-        let mut moves: Vec<u16> = Vec::new();
+    // every pseudo-legal move, the union of `generate_captures` and `generate_quiets`
+    pub fn generate_moves(&self, turn: Player) -> MoveList {
+        let mut moves = MoveList::new();
+        let (ally, _) = self.sides(turn);
+
+        self.generate_piece_moves(turn, ally.occupied, [u64::MAX; 6], &mut moves);
+        self.generate_castling(turn, &mut moves);
+        moves
+    }
+
+    // captures, en passant and every promotion, capturing or not
+    pub fn generate_captures(&self, turn: Player) -> MoveList {
+        let mut moves = MoveList::new();
+        let (ally, enemy) = self.sides(turn);
+
+        let mut targets = [enemy.occupied; 6];
+        targets[PieceType::Pawn as usize] |= enemy.double_push_pawns | RANK_1 | RANK_8;
+        self.generate_piece_moves(turn, ally.occupied, targets, &mut moves);
+        moves
+    }
+
+    // moves onto empty squares that do not promote, castling included
+    pub fn generate_quiets(&self, turn: Player) -> MoveList {
+        let mut moves = MoveList::new();
+        let (ally, enemy) = self.sides(turn);
+        let empty = !(ally.occupied | enemy.occupied);
+
+        let mut targets = [empty; 6];
+        targets[PieceType::Pawn as usize] &= !(enemy.double_push_pawns | RANK_1 | RANK_8);
+        self.generate_piece_moves(turn, ally.occupied, targets, &mut moves);
+        self.generate_castling(turn, &mut moves);
+        moves
+    }
+
+    // for a side in check: king steps, and with a single checker its capture or a block;
+    // a side not in check gets every move
+    pub fn generate_evasions(&self, turn: Player) -> MoveList {
+        let checkers = self.checkers(turn);
+        if checkers == 0 {
+            return self.generate_moves(turn);
+        }
+
+        let mut moves = MoveList::new();
+        let (ally, enemy) = self.sides(turn);
+
+        let block = if checkers.count_ones() == 1 {
+            checkers | BETWEEN[get_lsb(ally.kings)][get_lsb(checkers)]
+        } else {
+            0
+        };
+        let mut targets = [block; 6];
+        targets[PieceType::King as usize] = !ally.occupied;
 
-        let (ally , enemy) = if turn == Player::White {
+        // taking the lone checking pawn en passant
+        let pushed = if turn == Player::White { enemy.double_push_pawns >> 8 } else { enemy.double_push_pawns << 8 };
+        if pushed != 0 && pushed == checkers {
+            targets[PieceType::Pawn as usize] |= enemy.double_push_pawns;
+        }
+        self.generate_piece_moves(turn, ally.occupied, targets, &mut moves);
+        moves
+    }
+
+    // the moves of `generate_quiets` that give check
+    pub fn generate_quiet_checks(&self, turn: Player) -> MoveList {
+        let mut moves = MoveList::new();
+        let (ally, enemy) = self.sides(turn);
+        let empty = !(ally.occupied | enemy.occupied);
+        let info = self.check_info(turn);
+        let candidates = info.candidates;
+
+        // pieces that do not uncover a check must land on a square checking the king themselves
+        let mut targets = info.check_squares.map(|squares| squares & empty);
+        targets[PieceType::Pawn as usize] &= !(enemy.double_push_pawns | RANK_1 | RANK_8);
+        self.generate_piece_moves(turn, ally.occupied & !candidates, targets, &mut moves);
+
+        let mut others = MoveList::new();
+        let mut targets = [empty; 6];
+        targets[PieceType::Pawn as usize] &= !(enemy.double_push_pawns | RANK_1 | RANK_8);
+        self.generate_piece_moves(turn, candidates, targets, &mut others);
+        self.generate_castling(turn, &mut others);
+        for mov in others {
+            if self.gives_check_with(&info, mov) {
+                moves.push(mov);
+            }
+        }
+        moves
+    }
+
+    fn sides(&self, turn: Player) -> (&PieceSet, &PieceSet) {
+        if turn == Player::White {
             (&self.white_set, &self.black_set)
         } else {
             (&self.black_set, &self.white_set)
-        };
+        }
+    }
 
+    // moves of the pieces in `movers`, each piece type kept to its own `targets`
+    fn generate_piece_moves(&self, turn: Player, movers: u64, targets: [u64; 6], moves: &mut MoveList) {
+        let (ally, enemy) = self.sides(turn);
         let occupied = ally.occupied | enemy.occupied;
+        let targets = targets.map(|squares| squares & !ally.occupied);
 
         iterate_possible_move(
-            ally.bishops,
-            ally,
+            ally.bishops & movers,
             enemy,
+            targets[PieceType::Bishop as usize],
             0,
             |index, occupied| generate_diagonal_moves(index, *occupied),
             occupied,
-            &mut moves,
+            moves,
         );
 
         iterate_possible_move(
-            ally.queens,
-            ally,
+            ally.queens & movers,
             enemy,
+            targets[PieceType::Queen as usize],
             0,
             |index, occupied| generate_diagonal_moves(index, *occupied),
             occupied,
-            &mut moves,
+            moves,
         );
 
         iterate_possible_move(
-            ally.queens,
-            ally,
+            ally.queens & movers,
             enemy,
+            targets[PieceType::Queen as usize],
             0,
             |index, occupied| generate_straight_moves(index, *occupied),
             occupied,
-            &mut moves,
+            moves,
         );
 
         iterate_possible_move(
-            ally.rooks,
-            ally,
+            ally.rooks & movers,
             enemy,
+            targets[PieceType::Rook as usize],
             0,
             |index, occupied| generate_straight_moves(index, *occupied),
             occupied,
-            &mut moves,
+            moves,
         );
 
         iterate_possible_move(
-            ally.knights,
-            ally,
+            ally.knights & movers,
             enemy,
+            targets[PieceType::Knight as usize],
             0,
             |index, _| generate_knight_moves(index),
             (),
-            &mut moves,
+            moves,
         );
 
         iterate_possible_move(
-            ally.pawns,
-            ally,
+            ally.pawns & movers,
             enemy,
+            targets[PieceType::Pawn as usize],
             1,
             |index, args| {
                 let (occupied, turn, double_pawn_push) = *args;
                 generate_pawn_moves(index, occupied, turn as usize, double_pawn_push)
             },
             (occupied, turn, enemy.double_push_pawns),
-            &mut moves,
+            moves,
         );
 
         iterate_possible_move(
-            ally.kings,
-            ally,
+            ally.kings & movers,
             enemy,
-            2,
-            |index, args| {
-                let (occupied, castle_rooks, turn) = *args;
-                generate_king_moves(index, occupied, castle_rooks, turn, enemy.attack_map)
-            },
-            (occupied, ally.castle_rooks, turn),
-            &mut moves,
+            targets[PieceType::King as usize],
+            0,
+            |index, _| generate_king_attacks(index),
+            (),
+            moves,
         );
-
-        moves
     }
 
-    pub fn king_in_check<>( &self , turn : Player) -> bool {
-    
-        let ally : &PieceSet = if turn == Player::White  {&self.white_set} else {&self.black_set};
-        let enemy : &PieceSet = if turn == Player::White  {&self.black_set} else {&self.white_set};
-    
+    fn generate_castling(&self, turn: Player, moves: &mut MoveList) {
+        let (ally, enemy) = self.sides(turn);
         let king_index = get_lsb(ally.kings);
-        let enemey_king_index = get_lsb(enemy.kings);
-    
-    
-        if (1u64 << king_index) & KING_ATTACKS[enemey_king_index] != 0 {
-            return true;
-        }
-    
-        
-        if KNIGHT_JUMPS[king_index] & enemy.knights != 0 {
-            return  true;
+        if king_index != 64 {
+            generate_castle_moves(king_index, ally.occupied | enemy.occupied, ally.castle_rooks, enemy.attack_map, moves);
         }
-        
-        let occupied = ally.occupied | enemy.occupied;
-        let mut on_same_file : u64 = 0;
-        
-        for i in 0..4 {
-            on_same_file |= STRAIGHT_RAYS[i][king_index] & (enemy.rooks | enemy.queens);
+    }
+
+    // the moves of `self.player` that do not leave its king in check
+    pub fn legal_moves(&mut self) -> Vec<u16> {
+        let turn = self.player;
+        self.generate_attack_maps(turn);
+        self.generate_attack_maps(!turn);
+        self.generate_moves(turn).into_iter().filter(|mov| {
+            let mov_result = self.apply_move(turn, *mov);
+            let legal = !self.king_in_check(turn);
+            self.unapply_move(turn, *mov, mov_result);
+            legal
+        }).collect()
+    }
+
+    pub fn king_in_check(&self , turn : Player) -> bool {
+        self.checkers(turn) != 0
+    }
+
+    // pieces of either colour attacking `square` when the board holds `occupied`
+    pub fn attackers_to(&self , square : usize , occupied : u64) -> u64 {
+        let (white, black) = (&self.white_set, &self.black_set);
+
+        (PAWN_CAPTURES[Player::Black as usize][square] & white.pawns)
+            | (PAWN_CAPTURES[Player::White as usize][square] & black.pawns)
+            | (KNIGHT_JUMPS[square] & (white.knights | black.knights))
+            | (KING_ATTACKS[square] & (white.kings | black.kings))
+            | (generate_straight_moves(square, occupied) & (white.rooks | black.rooks | white.queens | black.queens))
+            | (generate_diagonal_moves(square, occupied) & (white.bishops | black.bishops | white.queens | black.queens))
+    }
+
+    // enemy pieces giving check to `player`
+    pub fn checkers(&self , player : Player) -> u64 {
+        let (ally, enemy) = if player == Player::White { (&self.white_set, &self.black_set) } else { (&self.black_set, &self.white_set) };
+        let king_index = get_lsb(ally.kings);
+        if king_index == 64 {
+            return 0;
         }
-        
+        self.attackers_to(king_index, ally.occupied | enemy.occupied) & enemy.occupied
+    }
+
+    // pieces of `player` that may not leave the line between their king and an enemy slider
+    pub fn pinned(&self , player : Player) -> u64 {
+        self.slider_blockers(player).0 & self.pieces(player).occupied
+    }
+
+    // enemy sliders pinning a piece of `player` to its king
+    pub fn pinners(&self , player : Player) -> u64 {
+        let (blockers, snipers) = self.slider_blockers(player);
+        let pinned = blockers & self.pieces(player).occupied;
+        let king_index = get_lsb(self.pieces(player).kings);
+
+        let mut pinners = 0;
+        let mut remaining = snipers;
         loop {
-            let index = get_lsb(on_same_file) ;
+            let index = get_lsb(remaining);
             if index == 64 {
                 break;
             }
-            
-            if generate_straight_moves(index, occupied) & ((1 as u64) << king_index) as u64 != 0 {
-                return true
+            if BETWEEN[king_index][index] & pinned != 0 {
+                pinners |= 1u64 << index;
             }
-            
-            flip_bit(&mut on_same_file, index);
+            flip_bit(&mut remaining, index);
         }
-        
-        let mut on_same_diagonal : u64 = 0;
-        for i in 0..4 {
-            on_same_diagonal |= DIAGONAL_RAYS[i][king_index] & (enemy.bishops | enemy.queens);
+        pinners
+    }
+
+    // pieces of `player` standing alone between one of its sliders and the enemy king,
+    // moving one off the line gives check
+    pub fn discovered_check_candidates(&self , player : Player) -> u64 {
+        self.slider_blockers(!player).0 & self.pieces(player).occupied
+    }
+
+    // for each piece type of `player`, the squares it would give check from, indexed by `PieceType as usize`
+    pub fn check_squares(&self , player : Player) -> [u64 ; 6] {
+        let king_index = get_lsb(self.pieces(!player).kings);
+        if king_index == 64 {
+            return [0 ; 6];
         }
-        
-        loop {
-            let index = get_lsb(on_same_diagonal);
-            if index == 64 {
-                break;
-            }
-            
-            if generate_diagonal_moves(index, occupied) & ((1 as u64 ) << king_index) as u64 != 0 {
-                return true;
-            }
-            flip_bit(&mut on_same_diagonal, index);
+
+        let occupied = self.white_set.occupied | self.black_set.occupied;
+        let diagonal = generate_diagonal_moves(king_index, occupied);
+        let straight = generate_straight_moves(king_index, occupied);
+        [
+            PAWN_CAPTURES[!player as usize][king_index],
+            KNIGHT_JUMPS[king_index],
+            diagonal,
+            straight,
+            diagonal | straight,
+            0,
+        ]
+    }
+
+    // the check squares and discovered check candidates of `turn`, for `gives_check_with`
+    pub fn check_info(&self , turn : Player) -> CheckInfo {
+        CheckInfo {
+            turn,
+            king_index: get_lsb(self.pieces(!turn).kings),
+            check_squares: self.check_squares(turn),
+            candidates: self.discovered_check_candidates(turn),
         }
-        
-        let attack_direction :i8 = if turn == Player::Black {-8 + king_index as i8 } else {8 + king_index as i8 };
-        
-        if attack_direction < 0 || attack_direction > 63 {
+    }
+
+    // whether the side to move checks the enemy king by playing the pseudo-legal `mov`;
+    // when testing many moves of one position, compute `check_info` once and use `gives_check_with`
+    pub fn gives_check(&self , mov : u16) -> bool {
+        self.gives_check_with(&self.check_info(self.player), mov)
+    }
+
+    // `gives_check` for a move of `info.turn`, `info` being the position's `check_info`
+    pub fn gives_check_with(&self , info : &CheckInfo , mov : u16) -> bool {
+        let turn = info.turn;
+        let (ally, enemy) = (self.pieces(turn), self.pieces(!turn));
+        let king_index = info.king_index;
+        if king_index == 64 {
             return false;
         }
-        
-        if king_index & 0b111 != 0b111  && test_bit(enemy.pawns , attack_direction as usize + 1){
-            return true;
+
+        let src = mov as usize & 0x3F;
+        let dest = (mov as usize >> 6) & 0x3F;
+        let special = mov >> 12;
+        let occupied = ally.occupied | enemy.occupied;
+
+        // castling and en passant move more than one piece, so look for sliders on the board as it will be
+        if special == CASTLE_KING || special == CASTLE_QUEEN {
+            let (king_dest, rook_dest) = castle_destinations(src, special == CASTLE_KING);
+            let after = (occupied & !(1u64 << src | 1u64 << dest)) | 1u64 << king_dest | 1u64 << rook_dest;
+            let rooks = (ally.rooks & !(1u64 << dest)) | 1u64 << rook_dest;
+            return generate_straight_moves(king_index, after) & (rooks | ally.queens) != 0
+                || generate_diagonal_moves(king_index, after) & (ally.bishops | ally.queens) != 0;
         }
-    
-        if king_index & 0b111 != 0  && test_bit(enemy.pawns , attack_direction as usize - 1){
+
+        if special == EN_PESSANT {
+            let captured = (src & 56) | (dest & 7);
+            let after = (occupied & !(1u64 << src | 1u64 << captured)) | 1u64 << dest;
+            return PAWN_CAPTURES[turn as usize][dest] & (1u64 << king_index) != 0
+                || generate_straight_moves(king_index, after) & (ally.rooks | ally.queens) != 0
+                || generate_diagonal_moves(king_index, after) & (ally.bishops | ally.queens) != 0;
+        }
+
+        if info.candidates & (1u64 << src) != 0 && LINE[src][king_index] & (1u64 << dest) == 0 {
             return true;
         }
-    
-        return false    
+
+        // a promoted piece may check through the square the pawn leaves
+        let vacated = occupied & !(1u64 << src);
+        let promoted = match special {
+            KNIGHT_PROMOTED => KNIGHT_JUMPS[dest],
+            BISHOP_PROMOTED => generate_diagonal_moves(dest, vacated),
+            ROOK_PROMOTED => generate_straight_moves(dest, vacated),
+            QUEEN_PROMOTED => generate_diagonal_moves(dest, vacated) | generate_straight_moves(dest, vacated),
+            _ => {
+                // the square left behind never lies between `dest` and the king, or the king would already be in check
+                return match self.mailbox[src] {
+                    Some((_, piece_type)) => info.check_squares[piece_type as usize] & (1u64 << dest) != 0,
+                    None => false,
+                };
+            }
+        };
+        promoted & (1u64 << king_index) != 0
+    }
+
+    fn pieces(&self , player : Player) -> &PieceSet {
+        if player == Player::White { &self.white_set } else { &self.black_set }
     }
-    
-    
 
-    pub fn run_engine<'a>(&mut self, mut turn: Player) {
+    // (the lone pieces of either colour between `king_owner`'s king and an enemy slider aimed at it, those sliders)
+    fn slider_blockers(&self , king_owner : Player) -> (u64, u64) {
+        let (ally, enemy) = (self.pieces(king_owner), self.pieces(!king_owner));
+        let king_index = get_lsb(ally.kings);
+        if king_index == 64 {
+            return (0, 0);
+        }
+
+        let occupied = ally.occupied | enemy.occupied;
+        let mut snipers = (generate_straight_moves(king_index, 0) & (enemy.rooks | enemy.queens))
+            | (generate_diagonal_moves(king_index, 0) & (enemy.bishops | enemy.queens));
+
+        let mut blockers = 0;
+        let mut aimed = 0;
         loop {
-            
-            self.generate_attack_maps(turn);
-            self.generate_attack_maps(!turn);
+            let index = get_lsb(snipers);
+            if index == 64 {
+                break;
+            }
+            let between = BETWEEN[king_index][index] & occupied;
+            if between.count_ones() == 1 {
+                blockers |= between;
+                aimed |= 1u64 << index;
+            }
+            flip_bit(&mut snipers, index);
+        }
+        (blockers, aimed)
+    }
+
+    // plays from `book` while it knows the position, and reads moves from stdin once it does not
+    pub fn run_engine(&mut self, turn: Player, mut book: Option<&mut PolyglotBook>) {
+        self.player = turn;
+        loop {
+            let status = self.game_status();
+            if status != GameStatus::Ongoing {
+                println!("{}", status);
+                return;
+            }
 
-            let moves = self.generate_moves(turn);
+            if let Some(book_move) = book.as_deref_mut().map(|book| book.pick(self)) {
+                match book_move {
+                    Ok(Some(mov)) => {
+                        println!("book move {}", move_to_uci(mov, false));
+                        self.make_move(mov);
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        println!("{}, leaving the book", e);
+                        book = None;
+                    }
+                }
+            }
 
             // get the best move from the moves
             // or in this case take input from the user
@@ -562,27 +887,73 @@ impl BitBoard {
 
             let mov = (src | (dest << 6) | (speical << 12)) as u16;
 
-            if !moves.contains(&mov) {
-                println!("Invalid move");
+            if let Err(e) = self.try_apply_move(mov) {
+                println!("{}", e);
                 continue;
             }
+        }
+    }
 
-            let mov_result: MoveResult = self.apply_move(turn, mov);
 
-            let in_check = self.king_in_check(turn);
+    // the pseudo-legal move of the side to move written as `text` in long algebraic notation
+    pub fn parse_uci_move(&mut self, text: &str, chess960: bool) -> Option<u16> {
+        let turn = self.player;
+        self.generate_attack_maps(turn);
+        self.generate_attack_maps(!turn);
+        self.generate_moves(turn).into_iter().find(|mov| move_to_uci(*mov, chess960) == text)
+    }
 
-            if in_check {
-                self.unapply_move(turn, mov, mov_result);
-                println!("Invalid move, king in check");
-                continue;
-            }
+    // plays `mov` for the side to move and remembers how to take it back; the move is not checked for legality
+    pub fn make_move(&mut self, mov: u16) {
+        let turn = self.player;
+        let src = mov as usize & 0x3F;
+        let dest = (mov as usize >> 6) & 0x3F;
+        let pawn_move = self.mailbox[src].is_some_and(|(_, piece_type)| piece_type == PieceType::Pawn);
+        // castling lands on the king's own rook, which is not a capture
+        let capture = self.mailbox[dest].is_some_and(|(player, _)| player != turn);
+        let resets_clock = pawn_move || capture;
 
-            turn = !turn;
+        let key = self.key();
+        let result = self.apply_move(turn, mov);
+        self.history.push(BoardState { mov, result, halfmove_clock: self.halfmove_clock, key });
+
+        self.halfmove_clock = if resets_clock { 0 } else { self.halfmove_clock + 1 };
+        if turn == Player::Black {
+            self.fullmove_number += 1;
         }
+        self.player = !turn;
     }
 
+    // takes back the last `make_move`, returning it, or None when there is nothing to take back
+    pub fn unmake_move(&mut self) -> Option<u16> {
+        let state = self.history.pop()?;
+        let turn = !self.player;
+
+        self.unapply_move(turn, state.mov, state.result);
+        self.halfmove_clock = state.halfmove_clock;
+        if turn == Player::Black {
+            self.fullmove_number -= 1;
+        }
+        self.player = turn;
+        Some(state.mov)
+    }
 
     pub fn apply_move(&mut self, turn: Player, mov: u16) -> MoveResult {
+        let mov_result = self.apply_move_unchecked(turn, mov);
+        self.update_keys(turn, mov, &mov_result);
+        debug_assert_eq!((self.piece_hash, self.pawn_hash), (self.piece_key(), self.pawn_key()));
+        debug_assert_eq!(self.check_mailbox(), Ok(()));
+        mov_result
+    }
+
+    pub fn unapply_move(&mut self, turn: Player, mov: u16, mov_result: MoveResult) {
+        self.update_keys(turn, mov, &mov_result);
+        self.unapply_move_unchecked(turn, mov, mov_result);
+        debug_assert_eq!((self.piece_hash, self.pawn_hash), (self.piece_key(), self.pawn_key()));
+        debug_assert_eq!(self.check_mailbox(), Ok(()));
+    }
+
+    fn apply_move_unchecked(&mut self, turn: Player, mov: u16) -> MoveResult {
         match mov >> 12 {
             KNIGHT_PROMOTED | BISHOP_PROMOTED | ROOK_PROMOTED | QUEEN_PROMOTED => {
                 let (promoted_piece_type, dest_piece_type, enemy_rooks, enemy_double_pawn_push) =
@@ -625,7 +996,7 @@ impl BitBoard {
         }
     }
 
-    pub fn unapply_move<'a>(&mut self, turn: Player, mov: u16, mov_result: MoveResult) {
+    fn unapply_move_unchecked(&mut self, turn: Player, mov: u16, mov_result: MoveResult) {
         match mov >> 12 {
             KNIGHT_PROMOTED | BISHOP_PROMOTED | ROOK_PROMOTED | QUEEN_PROMOTED => {
                 if let MoveResult::Promotions(
@@ -686,17 +1057,29 @@ impl BitBoard {
     }
 
     pub fn generate_attack_maps(&mut self, turn : Player) {
-        let mut attacks = 0;
-        let occupied = self.black_set.occupied | self.white_set.occupied;
+        let attacks = self.compute_attack_map(turn);
         let ally_pieces = if turn == Player::White {
             &mut self.white_set
         } else {
             &mut self.black_set
         };
 
+        ally_pieces.attack_map = attacks;
+    }
+
+    // every square attacked by `turn`, without touching the cached `attack_map`
+    pub fn compute_attack_map(&self, turn : Player) -> u64 {
+        let mut attacks = 0;
+        let occupied = self.black_set.occupied | self.white_set.occupied;
+        let ally_pieces = if turn == Player::White {
+            &self.white_set
+        } else {
+            &self.black_set
+        };
+
         attacks |= iterate_attack_moves(
             ally_pieces.pawns,
-            |index, turn| generate_pawn_attacks(index, &turn),
+            generate_pawn_attacks,
             turn,
         );
 
@@ -736,6 +1119,6 @@ impl BitBoard {
             occupied,
         );
 
-        ally_pieces.attack_map = attacks;
+        attacks
     }
 }
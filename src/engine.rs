@@ -1,5 +1,5 @@
 
-use crate::{bit_board::{BitBoard, PieceType} ,  piece_set::PieceSet, player::Player, utils::{ get_lsb, reset_bit, set_bit}};
+use crate::{bit_board::{BitBoard, PieceType} ,  piece_set::PieceSet, player::Player, utils::{reset_bit, set_bit}};
 
 
 const KNIGHT_PROMOTED : u16 = 1;
@@ -54,7 +54,7 @@ fn get_piece_bitset<'a>(pieces: &'a mut PieceSet, piece_type: &PieceType) -> &'a
 }
 
 
-pub fn apply_normal_move<'a>(board : &'a mut BitBoard , turn : Player , mov : u16) -> (PieceType, Option<PieceType> , u64 , u64, u64) {
+pub fn apply_normal_move(board : &mut BitBoard , turn : Player , mov : u16) -> (PieceType, Option<PieceType> , u64 , u64, u64) {
 
     let (ally_pieces,  enemy_pieces) = if turn == Player::White  {
         (&mut board.white_set, &mut board.black_set)
@@ -65,7 +65,10 @@ pub fn apply_normal_move<'a>(board : &'a mut BitBoard , turn : Player , mov : u1
     let src = mov as usize & 0x3F;
     let dest = (mov as usize >> 6) & 0x3F;
 
-    let src_piece_type  = get_piece_type(ally_pieces, src).unwrap();
+    // moves from outside the generator go through `try_apply_move`, which rejects an empty source square
+    let Some((_, src_piece_type)) = board.mailbox[src] else {
+        panic!("No piece on square {} to play move {:#06x}, use try_apply_move for unchecked moves", src, mov);
+    };
 
     let ally_rooks = ally_pieces.castle_rooks;
     let enemy_rooks = enemy_pieces.castle_rooks;
@@ -78,7 +81,9 @@ pub fn apply_normal_move<'a>(board : &'a mut BitBoard , turn : Player , mov : u1
         reset_bit(&mut ally_pieces.castle_rooks, src);
     }
 
-    let dest_piece_type = get_piece_type(enemy_pieces, dest);
+    let dest_piece_type = board.mailbox[dest].map(|(_, piece_type)| piece_type);
+    board.mailbox[dest] = board.mailbox[src];
+    board.mailbox[src] = None;
 
     let src_bitset = get_piece_bitset(ally_pieces, &src_piece_type);
 
@@ -104,7 +109,8 @@ pub fn apply_normal_move<'a>(board : &'a mut BitBoard , turn : Player , mov : u1
 }
 
 
-pub fn unapply_normal_move<'a>(board : &'a mut BitBoard , turn : Player , mov : u16, src_piece_type : PieceType, dest_piece_type : Option<PieceType> , ally_rooks : u64,
+#[allow(clippy::too_many_arguments)]
+pub fn unapply_normal_move(board : &mut BitBoard , turn : Player , mov : u16, src_piece_type : PieceType, dest_piece_type : Option<PieceType> , ally_rooks : u64,
         enemy_rooks : u64 , enemy_double_pawn_push : u64) {
     let (ally_pieces,  enemy_pieces) = if turn == Player::White  {
         (&mut board.white_set, &mut board.black_set)
@@ -134,44 +140,50 @@ pub fn unapply_normal_move<'a>(board : &'a mut BitBoard , turn : Player , mov :
     }
 
     enemy_pieces.double_push_pawns = enemy_double_pawn_push;
+
+    board.mailbox[src] = Some((turn, src_piece_type));
+    board.mailbox[dest] = dest_piece_type.map(|piece_type| (!turn, piece_type));
 }
 
 
-pub fn apply_castle_move<'a>(board : &'a mut BitBoard , turn : Player , mov : u16) -> (u64 , u64) {
+// where the king and rook end up: g- and f-file castling kingside, c- and d-file queenside
+pub fn castle_destinations(king_index : usize , kingside : bool) -> (usize , usize) {
+    let rank = king_index & 56;
+    if kingside {
+        (rank + 6, rank + 5)
+    } else {
+        (rank + 2, rank + 3)
+    }
+}
+
+// castling moves are encoded as the king taking its own rook
+pub fn apply_castle_move(board : &mut BitBoard , turn : Player , mov : u16) -> (u64 , u64) {
     let (ally_pieces,  enemy_pieces) = if turn == Player::White  {
         (&mut board.white_set, &mut board.black_set)
     } else {
         (&mut board.black_set, &mut board.white_set)
     };
 
-    let king_index = get_lsb(ally_pieces.kings);
-
-    if mov >> 12 == CASTLE_KING {
-        reset_bit(&mut ally_pieces.kings, king_index);
-        reset_bit(&mut ally_pieces.occupied, king_index);
-        
-        reset_bit(&mut ally_pieces.rooks, king_index + 3);
-        reset_bit(&mut ally_pieces.occupied, king_index + 3);
-        
-        set_bit(&mut ally_pieces.kings, king_index + 2);
-        set_bit(&mut ally_pieces.occupied, king_index + 2);
-
-        set_bit(&mut ally_pieces.rooks, king_index + 1);
-        set_bit(&mut ally_pieces.occupied, king_index + 1);
-        
-    } else {
-        reset_bit(&mut ally_pieces.kings, king_index);
-        reset_bit(&mut ally_pieces.occupied, king_index);
-        
-        reset_bit(&mut ally_pieces.rooks, king_index - 4);
-        reset_bit(&mut ally_pieces.occupied, king_index - 4);
-        
-        set_bit(&mut ally_pieces.kings, king_index - 2);
-        set_bit(&mut ally_pieces.occupied, king_index - 2);
-        
-        set_bit(&mut ally_pieces.rooks, king_index - 1);
-        set_bit(&mut ally_pieces.occupied, king_index - 1);
-    }
+    let king_index = mov as usize & 0x3F;
+    let rook_index = (mov as usize >> 6) & 0x3F;
+    let (king_dest, rook_dest) = castle_destinations(king_index, mov >> 12 == CASTLE_KING);
+
+    // both pieces come off first, in Chess960 either may land on the other's square
+    reset_bit(&mut ally_pieces.kings, king_index);
+    reset_bit(&mut ally_pieces.occupied, king_index);
+    reset_bit(&mut ally_pieces.rooks, rook_index);
+    reset_bit(&mut ally_pieces.occupied, rook_index);
+
+    set_bit(&mut ally_pieces.kings, king_dest);
+    set_bit(&mut ally_pieces.occupied, king_dest);
+    set_bit(&mut ally_pieces.rooks, rook_dest);
+    set_bit(&mut ally_pieces.occupied, rook_dest);
+
+    board.mailbox[king_index] = None;
+    board.mailbox[rook_index] = None;
+    board.mailbox[king_dest] = Some((turn, PieceType::King));
+    board.mailbox[rook_dest] = Some((turn, PieceType::Rook));
+
     let castle_rooks = ally_pieces.castle_rooks;
     ally_pieces.castle_rooks = 0;
     
@@ -183,39 +195,32 @@ pub fn apply_castle_move<'a>(board : &'a mut BitBoard , turn : Player , mov : u1
 }
 
 
-pub fn unapply_castle_move<'a>(board : &'a mut BitBoard , turn : Player , mov : u16 , castle_rooks : u64 , enemy_double_pawn_push : u64) {
+pub fn unapply_castle_move(board : &mut BitBoard , turn : Player , mov : u16 , castle_rooks : u64 , enemy_double_pawn_push : u64) {
     let (ally_pieces,  enemy_pieces) = if turn == Player::White  {
         (&mut board.white_set, &mut board.black_set)
     } else {
         (&mut board.black_set, &mut board.white_set)
     };
-    let king_index = get_lsb(ally_pieces.kings);
-
-    if mov >> 12 == CASTLE_KING {
-        reset_bit(&mut ally_pieces.kings, king_index );
-        reset_bit(&mut ally_pieces.occupied, king_index );
-        
-        reset_bit(&mut ally_pieces.rooks, king_index - 1);
-        reset_bit(&mut ally_pieces.occupied, king_index - 1);
-
-        set_bit(&mut ally_pieces.kings, king_index - 2);
-        set_bit(&mut ally_pieces.occupied, king_index - 2);
-        
-        set_bit(&mut ally_pieces.rooks, king_index + 1);
-        set_bit(&mut ally_pieces.occupied, king_index + 1);
-    } else {
-        reset_bit(&mut ally_pieces.kings, king_index);
-        reset_bit(&mut ally_pieces.occupied, king_index);
-        
-        reset_bit(&mut ally_pieces.rooks, king_index + 1);
-        reset_bit(&mut ally_pieces.occupied, king_index + 1);
-        
-        set_bit(&mut ally_pieces.rooks, king_index - 2);
-        set_bit(&mut ally_pieces.occupied, king_index - 2);
-        
-        set_bit(&mut ally_pieces.kings, king_index + 2);
-        set_bit(&mut ally_pieces.occupied, king_index + 2);
-    }
+
+    let king_index = mov as usize & 0x3F;
+    let rook_index = (mov as usize >> 6) & 0x3F;
+    let (king_dest, rook_dest) = castle_destinations(king_index, mov >> 12 == CASTLE_KING);
+
+    reset_bit(&mut ally_pieces.kings, king_dest);
+    reset_bit(&mut ally_pieces.occupied, king_dest);
+    reset_bit(&mut ally_pieces.rooks, rook_dest);
+    reset_bit(&mut ally_pieces.occupied, rook_dest);
+
+    set_bit(&mut ally_pieces.kings, king_index);
+    set_bit(&mut ally_pieces.occupied, king_index);
+    set_bit(&mut ally_pieces.rooks, rook_index);
+    set_bit(&mut ally_pieces.occupied, rook_index);
+
+    board.mailbox[king_dest] = None;
+    board.mailbox[rook_dest] = None;
+    board.mailbox[king_index] = Some((turn, PieceType::King));
+    board.mailbox[rook_index] = Some((turn, PieceType::Rook));
+
     ally_pieces.castle_rooks = castle_rooks;
     enemy_pieces.double_push_pawns = enemy_double_pawn_push;
 
@@ -232,7 +237,7 @@ pub fn apply_promotion(board : & mut BitBoard , turn : Player , mov : u16) ->  (
     let src = mov as usize & 0x3F;
     let dest = (mov as usize >> 6) & 0x3F;
 
-    let dest_piece_type = get_piece_type(&enemy_pieces, dest);
+    let dest_piece_type = board.mailbox[dest].map(|(_, piece_type)| piece_type);
 
     let promotion_type = match mov >> 12 {
         KNIGHT_PROMOTED => PieceType::Knight,
@@ -264,6 +269,9 @@ pub fn apply_promotion(board : & mut BitBoard , turn : Player , mov : u16) ->  (
     let enemy_double_push_pawns = enemy_pieces.double_push_pawns;
     enemy_pieces.double_push_pawns = 0;
 
+    board.mailbox[src] = None;
+    board.mailbox[dest] = Some((turn, promotion_type));
+
     (promotion_type , dest_piece_type , enemy_rooks , enemy_double_push_pawns)
 }
 
@@ -294,6 +302,9 @@ pub fn unapply_promotion(board : & mut BitBoard , turn : Player , mov : u16, pro
 
     enemy_pieces.castle_rooks = enemy_rooks;   
     enemy_pieces.double_push_pawns = enemy_double_pawn_push;
+
+    board.mailbox[src] = Some((turn, PieceType::Pawn));
+    board.mailbox[dest] = dest_piece_type.map(|piece_type| (!turn, piece_type));
 }
 
 pub fn apply_enpessant(board : & mut BitBoard , turn : Player , mov : u16) ->  (u64 , u64) {
@@ -318,6 +329,9 @@ pub fn apply_enpessant(board : & mut BitBoard , turn : Player , mov : u16) ->  (
     
     let enemy_double_push_pawns = enemy_pieces.double_push_pawns;
     enemy_pieces.double_push_pawns = 0;
+
+    board.mailbox.swap(src, dest);
+    board.mailbox[(dest as i32 + capture_direction) as usize] = None;
     (enemy_double_push_pawns , 0)
 }
 
@@ -344,10 +358,13 @@ pub fn unpply_enpessant(board : & mut BitBoard , turn : Player , mov : u16, enem
     set_bit(&mut enemy_pieces.occupied, (dest as i32 + capture_direction) as usize);
 
     enemy_pieces.double_push_pawns = enemy_double_push_pawns;
+
+    board.mailbox.swap(src, dest);
+    board.mailbox[(dest as i32 + capture_direction) as usize] = Some((!turn, PieceType::Pawn));
 }
 
 
-pub fn apply_double_pawn_push<'a>(board : & 'a mut BitBoard , turn : Player , mov : u16) -> u64{
+pub fn apply_double_pawn_push(board : & mut BitBoard , turn : Player , mov : u16) -> u64{
     let (ally_pieces,  enemy_pieces) = if turn == Player::White  {
         (&mut board.white_set, &mut board.black_set)
     } else {
@@ -369,6 +386,8 @@ pub fn apply_double_pawn_push<'a>(board : & 'a mut BitBoard , turn : Player , mo
     set_bit(&mut ally_pieces.pawns, dest);
     set_bit(&mut ally_pieces.occupied, dest);
     ally_pieces.double_push_pawns = double_push_pawns;
+    board.mailbox[src] = None;
+    board.mailbox[dest] = Some((turn, PieceType::Pawn));
 
     let enemy_double_push_pawns = enemy_pieces.double_push_pawns;
     enemy_pieces.double_push_pawns = 0;
@@ -376,7 +395,7 @@ pub fn apply_double_pawn_push<'a>(board : & 'a mut BitBoard , turn : Player , mo
 
 }
 
-pub fn unapply_double_pawn_push<'a>(board : & 'a mut BitBoard , turn : Player , mov : u16 , enemy_double_pawn_push : u64) {
+pub fn unapply_double_pawn_push(board : & mut BitBoard , turn : Player , mov : u16 , enemy_double_pawn_push : u64) {
     let (ally_pieces,  enemy_pieces) = if turn == Player::White  {
         (&mut board.white_set, &mut board.black_set)
     } else {
@@ -394,4 +413,6 @@ pub fn unapply_double_pawn_push<'a>(board : & 'a mut BitBoard , turn : Player ,
     enemy_pieces.double_push_pawns = enemy_double_pawn_push;
     set_bit(&mut ally_pieces.occupied, src);
     ally_pieces.double_push_pawns = 0;
+    board.mailbox[dest] = None;
+    board.mailbox[src] = Some((turn, PieceType::Pawn));
 }
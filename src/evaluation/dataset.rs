@@ -0,0 +1,40 @@
+use std::fs;
+
+
+// the result of a game for white, 1 / 0.5 / 0 for a win / draw / loss, found anywhere in `text`:
+// "1-0", "1/2-1/2" or "0-1", or a number, bare or in brackets as most extraction tools write it
+pub fn parse_result(text : &str) -> Option<f64> {
+    if text.contains("1/2-1/2") {
+        return Some(0.5);
+    }
+    if text.contains("1-0") {
+        return Some(1.0);
+    }
+    if text.contains("0-1") {
+        return Some(0.0);
+    }
+
+    let number = match text.find('[') {
+        Some(start) => {
+            let end = text[start..].find(']')? + start;
+            &text[start + 1..end]
+        }
+        None => text,
+    };
+    number.trim().parse::<f64>().ok().filter(|result| (0.0..=1.0).contains(result))
+}
+
+// parses every line of the file at `path`, skipping blank lines and lines starting with '#';
+// errors give the file and line they come from
+pub fn load_lines<T, F>(path : &str , parse : F) -> Result<Vec<T>, String>
+where
+    F : Fn(&str) -> Result<T, String>
+{
+    let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| parse(line).map_err(|e| format!("{}:{}: {}", path, number + 1, e)))
+        .collect()
+}
@@ -0,0 +1,200 @@
+use crate::{attack_maps::{KING_ATTACKS, KNIGHT_JUMPS}, bit_board::BitBoard, player::Player,
+move_generator::{generate_diagonal_moves, generate_straight_moves},
+utils::{flip_bit, get_lsb, FILE_A}};
+
+use super::{params::EvalParams, relative_rank, Score};
+
+
+// danger units added once per enemy piece that attacks the king zone, by piece type
+pub const KNIGHT_ATTACKER_WEIGHT : i32 = 81;
+pub const BISHOP_ATTACKER_WEIGHT : i32 = 52;
+pub const ROOK_ATTACKER_WEIGHT : i32 = 44;
+pub const QUEEN_ATTACKER_WEIGHT : i32 = 10;
+
+// danger units per attacked king zone square, counted once for every attacker
+pub const ZONE_ATTACK_WEIGHT : i32 = 69;
+
+// danger units when the enemy has a check available on a square we do not control
+pub const KNIGHT_SAFE_CHECK : i32 = 792;
+pub const BISHOP_SAFE_CHECK : i32 = 645;
+pub const ROOK_SAFE_CHECK : i32 = 1084;
+pub const QUEEN_SAFE_CHECK : i32 = 772;
+
+pub const NO_QUEEN_DANGER : i32 = -873;
+
+// indexed by the relative rank of our closest pawn in front of the king on that file, 0 when there is none
+pub const PAWN_SHIELD : [Score ; 8] = [
+    Score::new(-30, 0), Score::new(0, 0), Score::new(25, 0), Score::new(12, 0),
+    Score::new(4, 0), Score::new(0, 0), Score::new(0, 0), Score::new(0, 0),
+];
+
+// indexed by the relative rank (from our side) of their closest pawn in front of the king, 0 when there is none
+pub const PAWN_STORM : [Score ; 8] = [
+    Score::new(0, 0), Score::new(0, 0), Score::new(-45, -5), Score::new(-30, 0),
+    Score::new(-12, 0), Score::new(-4, 0), Score::new(0, 0), Score::new(0, 0),
+];
+
+// same, but the storming pawn is stuck in front of one of ours
+pub const BLOCKED_PAWN_STORM : [Score ; 8] = [
+    Score::new(0, 0), Score::new(0, 0), Score::new(-10, -10), Score::new(-6, -4),
+    Score::new(-2, 0), Score::new(0, 0), Score::new(0, 0), Score::new(0, 0),
+];
+
+pub const SEMI_OPEN_FILE_NEAR_KING : Score = Score::new(-15, 0);
+pub const OPEN_FILE_NEAR_KING : Score = Score::new(-25, 0);
+
+
+pub fn king_zone(king_index : usize) -> u64 {
+    KING_ATTACKS[king_index] | (1u64 << king_index)
+}
+
+// the king's own rank and every rank in front of it
+fn ranks_in_front(king_index : usize , player : Player) -> u64 {
+    let rank = king_index >> 3;
+    if player == Player::White { !0u64 << (rank * 8) } else { !0u64 >> ((7 - rank) * 8) }
+}
+
+fn closest_to_king(pawns : u64 , player : Player) -> Option<usize> {
+    if pawns == 0 {
+        None
+    } else if player == Player::White {
+        Some(get_lsb(pawns))
+    } else {
+        Some(63 - pawns.leading_zeros() as usize)
+    }
+}
+
+fn evaluate_king_files(board : &BitBoard , player : Player , king_index : usize , params : &EvalParams) -> Score {
+    let (ally, enemy) = if player == Player::White {
+        (&board.white_set, &board.black_set)
+    } else {
+        (&board.black_set, &board.white_set)
+    };
+
+    let king_file = king_index & 7;
+    let in_front = ranks_in_front(king_index, player);
+    let mut score = Score::default();
+
+    for file in king_file.saturating_sub(1)..=(king_file + 1).min(7) {
+        let file_mask = FILE_A << file;
+
+        if ally.pawns & file_mask == 0 {
+            score += if enemy.pawns & file_mask == 0 { params.open_file_near_king } else { params.semi_open_file_near_king };
+        }
+
+        let shield = closest_to_king(ally.pawns & file_mask & in_front, player);
+        score += params.pawn_shield[shield.map_or(0, |index| relative_rank(index, player))];
+
+        if let Some(index) = closest_to_king(enemy.pawns & file_mask & in_front, player) {
+            let blocked = shield.is_some_and(|shield| {
+                if player == Player::White { shield + 8 == index } else { index + 8 == shield }
+            });
+            let table = if blocked { &params.blocked_pawn_storm } else { &params.pawn_storm };
+            score += table[relative_rank(index, player)];
+        }
+    }
+
+    score
+}
+
+
+#[derive(Default)]
+struct ZoneAttacks {
+    attackers : i32,
+    danger : i32,
+    attacks : u64,
+}
+
+impl ZoneAttacks {
+    fn add<F>(&mut self, mut pieces : u64 , zone : u64 , weight : i32 , zone_attack_weight : i32 , generation_function : F)
+    where
+        F : Fn(usize) -> u64
+    {
+        loop {
+            let index = get_lsb(pieces);
+            if index == 64 {
+                break;
+            }
+            let attacks = generation_function(index);
+            self.attacks |= attacks;
+
+            let zone_attacks = (attacks & zone).count_ones() as i32;
+            if zone_attacks > 0 {
+                self.attackers += 1;
+                self.danger += weight + zone_attack_weight * zone_attacks;
+            }
+            flip_bit(&mut pieces, index);
+        }
+    }
+}
+
+
+// king danger is accumulated in units and turned into a quadratic penalty at the end
+pub fn evaluate_king_safety(board : &BitBoard , player : Player , params : &EvalParams) -> Score {
+    let (ally, enemy) = if player == Player::White {
+        (&board.white_set, &board.black_set)
+    } else {
+        (&board.black_set, &board.white_set)
+    };
+
+    let king_index = get_lsb(ally.kings);
+    if king_index == 64 {
+        return Score::default();
+    }
+
+    let occupied = ally.occupied | enemy.occupied;
+    let zone = king_zone(king_index);
+
+    let zone_weight = params.zone_attack_weight;
+
+    let mut knights = ZoneAttacks::default();
+    knights.add(enemy.knights, zone, params.knight_attacker_weight, zone_weight, |index| KNIGHT_JUMPS[index]);
+
+    let mut bishops = ZoneAttacks::default();
+    bishops.add(enemy.bishops, zone, params.bishop_attacker_weight, zone_weight, |index| generate_diagonal_moves(index, occupied));
+
+    let mut rooks = ZoneAttacks::default();
+    rooks.add(enemy.rooks, zone, params.rook_attacker_weight, zone_weight, |index| generate_straight_moves(index, occupied));
+
+    let mut queens = ZoneAttacks::default();
+    queens.add(enemy.queens, zone, params.queen_attacker_weight, zone_weight, |index| {
+        generate_diagonal_moves(index, occupied) | generate_straight_moves(index, occupied)
+    });
+
+    let mut score = evaluate_king_files(board, player, king_index, params);
+
+    let attackers = knights.attackers + bishops.attackers + rooks.attackers + queens.attackers;
+    let queen_count = enemy.queens.count_ones() as i32;
+    if attackers <= 1 - queen_count {
+        return score;
+    }
+
+    // checks the enemy could give from a square that is empty or ours and that we do not defend
+    let safe = !enemy.occupied & !board.compute_attack_map(player);
+    let diagonal_checks = generate_diagonal_moves(king_index, occupied) & safe;
+    let straight_checks = generate_straight_moves(king_index, occupied) & safe;
+
+    let mut danger = knights.danger + bishops.danger + rooks.danger + queens.danger;
+
+    if KNIGHT_JUMPS[king_index] & safe & knights.attacks != 0 {
+        danger += params.knight_safe_check;
+    }
+    if diagonal_checks & bishops.attacks != 0 {
+        danger += params.bishop_safe_check;
+    }
+    if straight_checks & rooks.attacks != 0 {
+        danger += params.rook_safe_check;
+    }
+    if (diagonal_checks | straight_checks) & queens.attacks != 0 {
+        danger += params.queen_safe_check;
+    }
+    if queen_count == 0 {
+        danger += params.no_queen_danger;
+    }
+
+    if danger > 0 {
+        score -= Score::new(danger * danger / 4096, danger / 16);
+    }
+
+    score
+}
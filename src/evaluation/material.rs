@@ -0,0 +1,237 @@
+use crate::{bit_board::BitBoard, piece_set::PieceSet, player::Player,
+utils::{get_lsb, DARK_SQUARES, FILE_A, FILE_H}};
+
+use super::{params::EvalParams, relative_rank, Score, BISHOP_VALUE, KNIGHT_VALUE, QUEEN_VALUE, ROOK_VALUE};
+
+
+pub const BISHOP_PAIR : Score = Score::new(30, 50);
+// for every rook after the first
+pub const REDUNDANT_ROOK : Score = Score::new(-10, -15);
+// queen and rook share much of their work
+pub const REDUNDANT_QUEEN : Score = Score::new(-8, -10);
+// per knight, per own pawn above five
+pub const KNIGHT_PAWN_ADJUSTMENT : Score = Score::new(3, 4);
+
+// endgame scale factors are out of SCALE_NORMAL
+pub const SCALE_NORMAL : i32 = 64;
+pub const SCALE_DRAW : i32 = 0;
+pub const SCALE_OPPOSITE_BISHOPS : i32 = 22;
+pub const SCALE_OPPOSITE_BISHOPS_WITH_PIECES : i32 = 46;
+pub const SCALE_PAWNLESS_EXCHANGE_UP : i32 = 14;
+
+
+// piece counts for both sides: pawns, knights, bishops, rooks, queens
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub struct MaterialSignature {
+    pub counts : [[u8 ; 5] ; 2],
+}
+
+impl MaterialSignature {
+    pub fn from_board(board : &BitBoard) -> Self {
+        let counts_for = |pieces : &PieceSet| [
+            pieces.pawns.count_ones() as u8,
+            pieces.knights.count_ones() as u8,
+            pieces.bishops.count_ones() as u8,
+            pieces.rooks.count_ones() as u8,
+            pieces.queens.count_ones() as u8,
+        ];
+        MaterialSignature {
+            counts : [counts_for(&board.white_set), counts_for(&board.black_set)],
+        }
+    }
+
+    // four bits per count, which covers every legal position
+    pub fn key(&self) -> u64 {
+        self.counts.iter().flatten().fold(0, |key, count| (key << 4) | (*count as u64 & 0xF))
+    }
+
+    pub fn pawns(&self , player : Player) -> i32 { self.counts[player as usize][0] as i32 }
+    pub fn knights(&self , player : Player) -> i32 { self.counts[player as usize][1] as i32 }
+    pub fn bishops(&self , player : Player) -> i32 { self.counts[player as usize][2] as i32 }
+    pub fn rooks(&self , player : Player) -> i32 { self.counts[player as usize][3] as i32 }
+    pub fn queens(&self , player : Player) -> i32 { self.counts[player as usize][4] as i32 }
+
+    // middlegame value of everything except pawns and the king, at the default piece values
+    // so that tuning them does not move the endgame rule boundaries
+    pub fn non_pawn_material(&self , player : Player) -> i32 {
+        self.knights(player) * KNIGHT_VALUE.mg
+            + self.bishops(player) * BISHOP_VALUE.mg
+            + self.rooks(player) * ROOK_VALUE.mg
+            + self.queens(player) * QUEEN_VALUE.mg
+    }
+}
+
+
+pub fn evaluate_imbalance(signature : &MaterialSignature , player : Player , params : &EvalParams) -> Score {
+    let mut score = Score::default();
+
+    if signature.bishops(player) >= 2 {
+        score += params.bishop_pair;
+    }
+    if signature.rooks(player) >= 2 {
+        score += params.redundant_rook * (signature.rooks(player) - 1);
+    }
+    if signature.queens(player) >= 1 && signature.rooks(player) >= 1 {
+        score += params.redundant_queen;
+    }
+    score += params.knight_pawn_adjustment * (signature.knights(player) * (signature.pawns(player) - 5));
+
+    score
+}
+
+
+// which endgame rule to try when a given side is ahead; the signature alone picks the rule,
+// the board decides whether it actually applies
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub enum ScaleRule {
+    #[default]
+    Normal,
+    OppositeBishops,
+    RookPawnWrongBishop,
+    PawnlessMinorAdvantage,
+}
+
+fn scale_rule_for(signature : &MaterialSignature , strong : Player) -> ScaleRule {
+    let weak = !strong;
+    let strong_pieces = signature.knights(strong) + signature.bishops(strong) + signature.rooks(strong) + signature.queens(strong);
+    let weak_pieces = signature.knights(weak) + signature.bishops(weak) + signature.rooks(weak) + signature.queens(weak);
+
+    if signature.pawns(strong) == 0
+        && signature.non_pawn_material(strong) - signature.non_pawn_material(weak) <= BISHOP_VALUE.mg {
+        return ScaleRule::PawnlessMinorAdvantage;
+    }
+
+    if signature.bishops(strong) == 1 && strong_pieces == 1 && weak_pieces == 0
+        && signature.pawns(strong) >= 1 && signature.pawns(weak) == 0 {
+        return ScaleRule::RookPawnWrongBishop;
+    }
+
+    if signature.bishops(strong) == 1 && signature.bishops(weak) == 1 {
+        return ScaleRule::OppositeBishops;
+    }
+
+    ScaleRule::Normal
+}
+
+
+pub fn scale_factor(board : &BitBoard , signature : &MaterialSignature , rule : ScaleRule , strong : Player , params : &EvalParams) -> i32 {
+    let (ally, enemy) = if strong == Player::White {
+        (&board.white_set, &board.black_set)
+    } else {
+        (&board.black_set, &board.white_set)
+    };
+
+    match rule {
+        ScaleRule::Normal => SCALE_NORMAL,
+
+        ScaleRule::OppositeBishops => {
+            let opposite = ((ally.bishops & DARK_SQUARES) == 0) != ((enemy.bishops & DARK_SQUARES) == 0);
+            if !opposite {
+                SCALE_NORMAL
+            } else if signature.non_pawn_material(strong) == BISHOP_VALUE.mg
+                && signature.non_pawn_material(!strong) == BISHOP_VALUE.mg {
+                params.scale_opposite_bishops
+            } else {
+                params.scale_opposite_bishops_with_pieces
+            }
+        }
+
+        ScaleRule::RookPawnWrongBishop => {
+            let on_a_file = ally.pawns & !FILE_A == 0;
+            let on_h_file = ally.pawns & !FILE_H == 0;
+            if !on_a_file && !on_h_file {
+                return SCALE_NORMAL;
+            }
+
+            let corner = match (strong, on_a_file) {
+                (Player::White, true) => 56,
+                (Player::White, false) => 63,
+                (Player::Black, true) => 0,
+                (Player::Black, false) => 7,
+            };
+            let bishop_controls_corner = ((ally.bishops & DARK_SQUARES) != 0) == ((1u64 << corner) & DARK_SQUARES != 0);
+            let weak_king = get_lsb(enemy.kings);
+            let king_in_corner = weak_king != 64
+                && (weak_king & 7).abs_diff(corner & 7) <= 1
+                && relative_rank(weak_king, strong) >= 6;
+
+            if !bishop_controls_corner && king_in_corner { SCALE_DRAW } else { SCALE_NORMAL }
+        }
+
+        ScaleRule::PawnlessMinorAdvantage => {
+            if signature.non_pawn_material(strong) <= BISHOP_VALUE.mg {
+                SCALE_DRAW
+            } else {
+                params.scale_pawnless_exchange_up
+            }
+        }
+    }
+}
+
+
+// everything that depends on the material signature alone
+#[derive(Clone, Copy)]
+pub struct MaterialEntry {
+    pub key : u64,
+    pub signature : MaterialSignature,
+    pub imbalance : [Score ; 2],
+    pub scale_rules : [ScaleRule ; 2],
+}
+
+// signature keys only use the low 40 bits, so this never matches a real position
+const EMPTY_MATERIAL_ENTRY : MaterialEntry = MaterialEntry {
+    key : u64::MAX,
+    signature : MaterialSignature { counts : [[0 ; 5] ; 2] },
+    imbalance : [Score::new(0, 0) ; 2],
+    scale_rules : [ScaleRule::Normal ; 2],
+};
+
+pub fn evaluate_material_signature(signature : MaterialSignature , params : &EvalParams) -> MaterialEntry {
+    MaterialEntry {
+        key : signature.key(),
+        signature,
+        imbalance : [
+            evaluate_imbalance(&signature, Player::White, params),
+            evaluate_imbalance(&signature, Player::Black, params),
+        ],
+        scale_rules : [
+            scale_rule_for(&signature, Player::White),
+            scale_rule_for(&signature, Player::Black),
+        ],
+    }
+}
+
+
+pub struct MaterialHashTable {
+    entries : Vec<MaterialEntry>,
+    mask : usize,
+}
+
+impl MaterialHashTable {
+    // `size` is rounded up to a power of two
+    pub fn new(size : usize) -> Self {
+        let size = size.max(1).next_power_of_two();
+        MaterialHashTable {
+            entries : vec![EMPTY_MATERIAL_ENTRY ; size],
+            mask : size - 1,
+        }
+    }
+
+    // like the pawn table, entries are only valid for the `params` they were computed with
+    pub fn probe(&mut self, board : &BitBoard , params : &EvalParams) -> &MaterialEntry {
+        let signature = MaterialSignature::from_board(board);
+        let key = signature.key();
+        // fold the high bits in, signatures differ mostly in the low nibbles
+        let slot = (key ^ (key >> 20)) as usize & self.mask;
+
+        if self.entries[slot].key != key {
+            self.entries[slot] = evaluate_material_signature(signature, params);
+        }
+
+        &self.entries[slot]
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(EMPTY_MATERIAL_ENTRY);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{attack_maps::KNIGHT_JUMPS, bit_board::BitBoard, piece_set::PieceSet, player::Player,
+move_generator::{generate_diagonal_moves, generate_straight_moves},
+utils::{flip_bit, get_lsb, DARK_SQUARES, FILE_A, LIGHT_SQUARES}};
+
+use super::pawns::{forward_fill, pawn_attacks, push};
+use super::{params::EvalParams, relative_rank, Score};
+
+
+// indexed by the number of reachable squares inside the mobility area
+pub const KNIGHT_MOBILITY : [Score ; 9] = [
+    Score::new(-31, -40), Score::new(-26, -28), Score::new(-6, -15), Score::new(-2, -8),
+    Score::new(1, 2), Score::new(6, 5), Score::new(11, 8), Score::new(14, 10),
+    Score::new(16, 12),
+];
+
+pub const BISHOP_MOBILITY : [Score ; 14] = [
+    Score::new(-24, -29), Score::new(-10, -11), Score::new(8, -1), Score::new(13, 6),
+    Score::new(19, 12), Score::new(25, 21), Score::new(27, 27), Score::new(31, 28),
+    Score::new(31, 32), Score::new(34, 36), Score::new(40, 39), Score::new(40, 43),
+    Score::new(45, 44), Score::new(49, 48),
+];
+
+pub const ROOK_MOBILITY : [Score ; 15] = [
+    Score::new(-29, -38), Score::new(-13, -9), Score::new(-7, 14), Score::new(-5, 27),
+    Score::new(-2, 34), Score::new(-1, 41), Score::new(4, 56), Score::new(8, 59),
+    Score::new(15, 66), Score::new(14, 71), Score::new(16, 77), Score::new(19, 82),
+    Score::new(23, 83), Score::new(24, 84), Score::new(29, 85),
+];
+
+pub const QUEEN_MOBILITY : [Score ; 28] = [
+    Score::new(-19, -18), Score::new(-10, -7), Score::new(1, 4), Score::new(1, 9),
+    Score::new(7, 17), Score::new(11, 27), Score::new(14, 30), Score::new(20, 36),
+    Score::new(21, 39), Score::new(24, 46), Score::new(28, 47), Score::new(30, 52),
+    Score::new(30, 56), Score::new(33, 60), Score::new(33, 61), Score::new(35, 63),
+    Score::new(35, 66), Score::new(36, 68), Score::new(39, 70), Score::new(44, 71),
+    Score::new(44, 74), Score::new(49, 83), Score::new(51, 85), Score::new(51, 87),
+    Score::new(53, 92), Score::new(54, 95), Score::new(56, 103), Score::new(58, 106),
+];
+
+pub const ROOK_OPEN_FILE : Score = Score::new(22, 10);
+pub const ROOK_SEMI_OPEN_FILE : Score = Score::new(10, 4);
+pub const ROOK_ON_SEVENTH : Score = Score::new(10, 20);
+pub const KNIGHT_OUTPOST : Score = Score::new(28, 12);
+pub const BISHOP_OUTPOST : Score = Score::new(14, 6);
+// per own pawn on the bishop's colour; blocked pawns count twice
+pub const BAD_BISHOP_PAWN : Score = Score::new(-2, -4);
+pub const TRAPPED_ROOK : Score = Score::new(-40, -5);
+pub const TRAPPED_BISHOP : Score = Score::new(-80, -80);
+
+
+fn sides(board : &BitBoard , player : Player) -> (&PieceSet , &PieceSet) {
+    if player == Player::White {
+        (&board.white_set, &board.black_set)
+    } else {
+        (&board.black_set, &board.white_set)
+    }
+}
+
+// own pawns that cannot move forward
+pub fn blocked_pawns(board : &BitBoard , player : Player) -> u64 {
+    let (ally, enemy) = sides(board, player);
+    ally.pawns & push(ally.occupied | enemy.occupied, !player)
+}
+
+// squares worth counting for mobility: not covered by enemy pawns and not stuck behind our own pawns
+pub fn mobility_area(board : &BitBoard , player : Player) -> u64 {
+    let (_, enemy) = sides(board, player);
+    !(pawn_attacks(enemy.pawns, !player) | blocked_pawns(board, player))
+}
+
+fn sum_mobility<F>(mut pieces : u64 , area : u64 , table : &[Score] , generation_function : F) -> Score
+where
+    F : Fn(usize) -> u64
+{
+    let mut score = Score::default();
+    loop {
+        let index = get_lsb(pieces);
+        if index == 64 {
+            break;
+        }
+        score += table[(generation_function(index) & area).count_ones() as usize];
+        flip_bit(&mut pieces, index);
+    }
+    score
+}
+
+pub fn evaluate_mobility(board : &BitBoard , player : Player , params : &EvalParams) -> Score {
+    let (ally, _) = sides(board, player);
+    let occupied = board.white_set.occupied | board.black_set.occupied;
+    let area = mobility_area(board, player);
+
+    sum_mobility(ally.knights, area, &params.knight_mobility, |index| KNIGHT_JUMPS[index])
+        + sum_mobility(ally.bishops, area, &params.bishop_mobility, |index| generate_diagonal_moves(index, occupied))
+        + sum_mobility(ally.rooks, area, &params.rook_mobility, |index| generate_straight_moves(index, occupied))
+        + sum_mobility(ally.queens, area, &params.queen_mobility, |index| {
+            generate_diagonal_moves(index, occupied) | generate_straight_moves(index, occupied)
+        })
+}
+
+
+// a rook boxed in on the back rank by its own uncastled king
+fn is_trapped_rook(board : &BitBoard , player : Player , index : usize) -> bool {
+    let (ally, _) = sides(board, player);
+    let king_index = get_lsb(ally.kings);
+    if king_index == 64 || relative_rank(index, player) != 0 || relative_rank(king_index, player) != 0 {
+        return false;
+    }
+
+    let occupied = board.white_set.occupied | board.black_set.occupied;
+    let king_file = king_index & 7;
+    let rook_file = index & 7;
+    let boxed_in = (king_file >= 4 && rook_file > king_file) || (king_file <= 3 && rook_file < king_file);
+
+    boxed_in && (generate_straight_moves(index, occupied) & mobility_area(board, player)).count_ones() <= 3
+}
+
+// maps a square to how it looks from white's side of the board, and back
+fn flip_for(index : usize , player : Player) -> usize {
+    if player == Player::White { index } else { index ^ 56 }
+}
+
+// the classic Bxa7 b6 pattern, its mirror on the h-file and the same one rank lower
+fn is_trapped_bishop(board : &BitBoard , player : Player , index : usize) -> bool {
+    let (_, enemy) = sides(board, player);
+    let blocker = match flip_for(index, player) {
+        48 => 41,
+        55 => 46,
+        40 => 33,
+        47 => 38,
+        _ => return false,
+    };
+    enemy.pawns & (1u64 << flip_for(blocker, player)) != 0
+}
+
+pub fn evaluate_piece_placement(board : &BitBoard , player : Player , params : &EvalParams) -> Score {
+    let (ally, enemy) = sides(board, player);
+    let mut score = Score::default();
+
+    let own_pawn_support = pawn_attacks(ally.pawns, player);
+    let enemy_pawn_reach = forward_fill(pawn_attacks(enemy.pawns, !player), !player);
+
+    let mut rooks = ally.rooks;
+    loop {
+        let index = get_lsb(rooks);
+        if index == 64 {
+            break;
+        }
+        let file_mask = FILE_A << (index & 7);
+        if ally.pawns & file_mask == 0 {
+            score += if enemy.pawns & file_mask == 0 { params.rook_open_file } else { params.rook_semi_open_file };
+        }
+
+        // on the 7th it matters only with pawns to eat there or the king cut off on the 8th
+        let rank = relative_rank(index, player);
+        if rank == 6 {
+            let seventh = 0xFFu64 << (index & !7);
+            let eighth = push(seventh, player);
+            if seventh & enemy.pawns != 0 || eighth & enemy.kings != 0 {
+                score += params.rook_on_seventh;
+            }
+        }
+
+        if is_trapped_rook(board, player, index) {
+            score += params.trapped_rook;
+        }
+        flip_bit(&mut rooks, index);
+    }
+
+    let outposts = own_pawn_support & !enemy_pawn_reach;
+    let mut minors = ally.knights | ally.bishops;
+    loop {
+        let index = get_lsb(minors);
+        if index == 64 {
+            break;
+        }
+        let rank = relative_rank(index, player);
+        let is_knight = ally.knights & (1u64 << index) != 0;
+
+        if (3..=5).contains(&rank) && outposts & (1u64 << index) != 0 {
+            score += if is_knight { params.knight_outpost } else { params.bishop_outpost };
+        }
+
+        if !is_knight {
+            let colour = if DARK_SQUARES & (1u64 << index) != 0 { DARK_SQUARES } else { LIGHT_SQUARES };
+            let same_colour = (ally.pawns & colour).count_ones() as i32;
+            let blocked = (blocked_pawns(board, player) & colour).count_ones() as i32;
+            score += params.bad_bishop_pawn * (same_colour + blocked);
+
+            if is_trapped_bishop(board, player, index) {
+                score += params.trapped_bishop;
+            }
+        }
+        flip_bit(&mut minors, index);
+    }
+
+    score
+}
@@ -0,0 +1,421 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{bit_board::BitBoard, piece_set::PieceSet, player::Player, utils::{flip_bit, get_lsb}};
+
+use self::king_safety::evaluate_king_safety;
+use self::material::{evaluate_material_signature, scale_factor, MaterialEntry, MaterialHashTable, MaterialSignature, SCALE_NORMAL};
+use self::mobility::{evaluate_mobility, evaluate_piece_placement};
+use self::nnue::{Network, NnueState};
+use self::params::{EvalParams, DEFAULT_PARAMS};
+use self::trace::{EvalTrace, TermTrace};
+use self::pawns::{evaluate_passed_pawns, evaluate_pawn_structure, PawnEntry, PawnHashTable};
+
+pub mod pawns;
+pub mod king_safety;
+pub mod mobility;
+pub mod material;
+pub mod nnue;
+pub mod nnue_training;
+pub mod params;
+pub mod tuning;
+pub mod dataset;
+pub mod trace;
+
+
+// middlegame / endgame pair, blended by game phase at the end of the evaluation
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    pub mg : i32,
+    pub eg : i32,
+}
+
+impl Score {
+    pub const fn new(mg : i32 , eg : i32) -> Self {
+        Score { mg, eg }
+    }
+}
+
+impl Add for Score {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl Sub for Score {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self::Output {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Neg for Score {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Score::new(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Self;
+
+    fn mul(self, factor: i32) -> Self::Output {
+        Score::new(self.mg * factor, self.eg * factor)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+
+pub const PAWN_VALUE : Score = Score::new(100, 120);
+pub const KNIGHT_VALUE : Score = Score::new(320, 300);
+pub const BISHOP_VALUE : Score = Score::new(330, 310);
+pub const ROOK_VALUE : Score = Score::new(500, 540);
+pub const QUEEN_VALUE : Score = Score::new(950, 1000);
+
+pub const TEMPO : Score = Score::new(20, 10);
+
+const KNIGHT_PHASE : i32 = 1;
+const BISHOP_PHASE : i32 = 1;
+const ROOK_PHASE : i32 = 2;
+const QUEEN_PHASE : i32 = 4;
+pub const MAX_PHASE : i32 = 24;
+
+const PAWN_TABLE_ENTRIES : usize = 1 << 14;
+const MATERIAL_TABLE_ENTRIES : usize = 1 << 12;
+
+// piece-square tables are written as seen from white with rank 8 on top,
+// so white looks squares up with `index ^ 56` and black with `index`
+#[rustfmt::skip]
+const PAWN_TABLE : [i32 ; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE : [i32 ; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE : [i32 ; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE : [i32 ; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE : [i32 ; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_MG_TABLE : [i32 ; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_EG_TABLE : [i32 ; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+
+pub fn relative_square(index : usize , player : Player) -> usize {
+    if player == Player::White { index ^ 56 } else { index }
+}
+
+pub fn relative_rank(index : usize , player : Player) -> usize {
+    if player == Player::White { index >> 3 } else { 7 - (index >> 3) }
+}
+
+
+fn evaluate_pieces(mut pieces : u64 , player : Player , value : Score , mg_table : &[i32 ; 64] , eg_table : &[i32 ; 64]) -> Score {
+    let mut score = Score::default();
+    loop {
+        let index = get_lsb(pieces);
+        if index == 64 {
+            break;
+        }
+        let square = relative_square(index, player);
+        score += value + Score::new(mg_table[square], eg_table[square]);
+        flip_bit(&mut pieces, index);
+    }
+    score
+}
+
+pub fn evaluate_material_and_psqt(pieces : &PieceSet , player : Player , params : &EvalParams) -> Score {
+    evaluate_pieces(pieces.pawns, player, params.pawn_value, &params.pawn_table, &params.pawn_table)
+        + evaluate_pieces(pieces.knights, player, params.knight_value, &params.knight_table, &params.knight_table)
+        + evaluate_pieces(pieces.bishops, player, params.bishop_value, &params.bishop_table, &params.bishop_table)
+        + evaluate_pieces(pieces.rooks, player, params.rook_value, &params.rook_table, &params.rook_table)
+        + evaluate_pieces(pieces.queens, player, params.queen_value, &params.queen_table, &params.queen_table)
+        + evaluate_pieces(pieces.kings, player, Score::default(), &params.king_mg_table, &params.king_eg_table)
+}
+
+// piece values alone, without the square bonuses
+pub fn evaluate_material(pieces : &PieceSet , params : &EvalParams) -> Score {
+    params.pawn_value * pieces.pawns.count_ones() as i32
+        + params.knight_value * pieces.knights.count_ones() as i32
+        + params.bishop_value * pieces.bishops.count_ones() as i32
+        + params.rook_value * pieces.rooks.count_ones() as i32
+        + params.queen_value * pieces.queens.count_ones() as i32
+}
+
+// 24 with all minor and major pieces on the board, 0 in a pawn ending
+pub fn game_phase(board : &BitBoard) -> i32 {
+    let mut phase = 0;
+    for pieces in [&board.white_set, &board.black_set] {
+        phase += pieces.knights.count_ones() as i32 * KNIGHT_PHASE;
+        phase += pieces.bishops.count_ones() as i32 * BISHOP_PHASE;
+        phase += pieces.rooks.count_ones() as i32 * ROOK_PHASE;
+        phase += pieces.queens.count_ones() as i32 * QUEEN_PHASE;
+    }
+    phase.min(MAX_PHASE)
+}
+
+pub fn taper(score : Score , phase : i32) -> i32 {
+    (score.mg * phase + score.eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+
+// everything past the pawn and material lookups, which the caller may have cached;
+// the evaluation is the last line of the trace, so the two cannot disagree
+fn trace_with_entries(board : &BitBoard , pawn_entry : &PawnEntry , material_entry : &MaterialEntry , params : &EvalParams) -> EvalTrace {
+    let per_side = |f : &dyn Fn(Player) -> Score| TermTrace::new(f(Player::White), f(Player::Black));
+    let pieces_of = |player : Player| if player == Player::White { &board.white_set } else { &board.black_set };
+
+    let material = per_side(&|player| evaluate_material(pieces_of(player), params));
+    let with_squares = per_side(&|player| evaluate_material_and_psqt(pieces_of(player), player, params));
+    let psqt = TermTrace::new(with_squares.white - material.white, with_squares.black - material.black);
+    let pawns = per_side(&|player| pawn_entry.scores[player as usize]);
+    let passed_pawns = per_side(&|player| evaluate_passed_pawns(board, pawn_entry, player, params));
+    let king_safety = per_side(&|player| evaluate_king_safety(board, player, params));
+    let mobility = per_side(&|player| evaluate_mobility(board, player, params));
+    let pieces = per_side(&|player| evaluate_piece_placement(board, player, params));
+    let imbalance = per_side(&|player| material_entry.imbalance[player as usize]);
+    let tempo = per_side(&|player| if player == board.player { params.tempo } else { Score::default() });
+
+    let total = [material, psqt, pawns, passed_pawns, king_safety, mobility, pieces, imbalance, tempo]
+        .iter()
+        .fold(Score::default(), |total, term| total + term.total());
+
+    // drawish endings only shrink the endgame half, towards zero
+    let strong = if total.eg >= 0 { Player::White } else { Player::Black };
+    let scale = scale_factor(board, &material_entry.signature, material_entry.scale_rules[strong as usize], strong, params);
+    let scaled = Score::new(total.mg, total.eg * scale / SCALE_NORMAL);
+
+    let phase = game_phase(board);
+    let blended = taper(scaled, phase);
+
+    EvalTrace {
+        material, psqt, pawns, passed_pawns, king_safety, mobility, pieces, imbalance, tempo,
+        total,
+        scale_factor : scale,
+        scaled_eg : scaled.eg,
+        phase,
+        blended,
+        eval : if board.player == Player::White { blended } else { -blended },
+    }
+}
+
+fn evaluate_with_entries(board : &BitBoard , pawn_entry : &PawnEntry , material_entry : &MaterialEntry , params : &EvalParams) -> i32 {
+    trace_with_entries(board, pawn_entry, material_entry, params).eval
+}
+
+
+// holds the caches that make repeated evaluations cheap; a search should keep one around
+pub struct Evaluator {
+    pawn_table : PawnHashTable,
+    material_table : MaterialHashTable,
+    params : EvalParams,
+    nnue : Option<NnueState>,
+    use_nnue : bool,
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Evaluator {
+            pawn_table : PawnHashTable::new(PAWN_TABLE_ENTRIES),
+            material_table : MaterialHashTable::new(MATERIAL_TABLE_ENTRIES),
+            params : DEFAULT_PARAMS,
+            nnue : None,
+            use_nnue : false,
+        }
+    }
+
+    pub fn params(&self) -> &EvalParams {
+        &self.params
+    }
+
+    // cached entries were computed with the old weights, so both tables start over
+    pub fn set_params(&mut self, params : EvalParams) {
+        self.params = params;
+        self.pawn_table.clear();
+        self.material_table.clear();
+    }
+
+    pub fn load_params(&mut self, path : &str) -> Result<(), String> {
+        self.set_params(EvalParams::load(path)?);
+        Ok(())
+    }
+
+    // engine options as a UCI front end would pass them on; the NNUE ones need a board
+    // to set up the accumulators
+    pub fn set_option(&mut self, name : &str , value : &str , board : &BitBoard) -> Result<(), String> {
+        match name {
+            "EvalParams" => {
+                if value.is_empty() || value == "<default>" {
+                    self.set_params(DEFAULT_PARAMS);
+                    Ok(())
+                } else {
+                    self.load_params(value)
+                }
+            }
+            "EvalFile" => self.load_network(value, board),
+            "UseNNUE" => match value {
+                "true" => { self.set_use_nnue(true); Ok(()) }
+                "false" => { self.set_use_nnue(false); Ok(()) }
+                _ => Err(format!("Invalid value for UseNNUE: {}", value)),
+            },
+            _ => Err(format!("Unknown option {}", name)),
+        }
+    }
+
+    // the handcrafted evaluation term by term, whether or not NNUE is in use
+    pub fn trace(&mut self, board : &BitBoard) -> EvalTrace {
+        let pawn_entry = self.pawn_table.probe(board, &self.params);
+        let material_entry = self.material_table.probe(board, &self.params);
+        trace_with_entries(board, pawn_entry, material_entry, &self.params)
+    }
+
+    // loads a network and switches to it; the handcrafted evaluation stays available
+    pub fn load_network(&mut self, path : &str , board : &BitBoard) -> Result<(), String> {
+        self.set_network(Network::load(path)?, board);
+        Ok(())
+    }
+
+    pub fn set_network(&mut self, network : Network , board : &BitBoard) {
+        self.nnue = Some(NnueState::new(network, board));
+        self.use_nnue = true;
+    }
+
+    // has no effect until a network is loaded
+    pub fn set_use_nnue(&mut self, use_nnue : bool) {
+        self.use_nnue = use_nnue;
+    }
+
+    pub fn uses_nnue(&self) -> bool {
+        self.use_nnue && self.nnue.is_some()
+    }
+
+    // moves made with `make_move` are picked up by `evaluate` on its own; a search using the
+    // low-level `apply_move` makes its moves through this instead so the accumulators follow the board
+    pub fn nnue_mut(&mut self) -> Option<&mut NnueState> {
+        self.nnue.as_mut()
+    }
+
+    // score in centipawns from the point of view of `board.player`
+    pub fn evaluate(&mut self, board : &BitBoard) -> i32 {
+        if self.use_nnue {
+            if let Some(nnue) = &mut self.nnue {
+                return nnue.evaluate(board);
+            }
+        }
+
+        let pawn_entry = self.pawn_table.probe(board, &self.params);
+        let material_entry = self.material_table.probe(board, &self.params);
+        evaluate_with_entries(board, pawn_entry, material_entry, &self.params)
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+// uncached evaluation, for one-off calls outside of a search
+pub fn evaluate(board : &BitBoard) -> i32 {
+    evaluate_with_params(board, &DEFAULT_PARAMS)
+}
+
+pub fn evaluate_with_params(board : &BitBoard , params : &EvalParams) -> i32 {
+    evaluate_trace_with_params(board, params).eval
+}
+
+pub fn evaluate_trace(board : &BitBoard) -> EvalTrace {
+    evaluate_trace_with_params(board, &DEFAULT_PARAMS)
+}
+
+pub fn evaluate_trace_with_params(board : &BitBoard , params : &EvalParams) -> EvalTrace {
+    let pawn_entry = evaluate_pawn_structure(board.white_set.pawns, board.black_set.pawns, board.pawn_hash, params);
+    let material_entry = evaluate_material_signature(MaterialSignature::from_board(board), params);
+    trace_with_entries(board, &pawn_entry, &material_entry, params)
+}
@@ -0,0 +1,574 @@
+use std::fs;
+
+use crate::{bit_board::{BitBoard, MoveResult, PieceType}, engine::castle_destinations, piece_set::PieceSet, player::Player,
+utils::{flip_bit, get_lsb}, zobrist::piece_key_change};
+
+
+// file layout, all little endian:
+//   magic "RCNN", version u32, feature set u32 (0 = HalfKP, 1 = HalfKA), hidden size u32,
+//   feature weights i16 [features * hidden], feature biases i16 [hidden],
+//   output weights i16 [2 * hidden] (side to move first), output bias i32
+pub const NNUE_MAGIC : [u8 ; 4] = *b"RCNN";
+pub const NNUE_VERSION : u32 = 1;
+const HEADER_SIZE : usize = 16;
+
+// quantization: accumulator values are clipped to [0, QA], output weights are scaled by QB
+pub const QA : i32 = 255;
+pub const QB : i32 = 64;
+pub const OUTPUT_SCALE : i32 = 400;
+
+const CASTLE_KING : u16 = 6;
+
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FeatureSet {
+    // king square x (10 non-king pieces) x square
+    HalfKP,
+    // king square x (12 pieces, kings included) x square
+    HalfKA,
+}
+
+impl FeatureSet {
+    pub fn from_id(id : u32) -> Option<Self> {
+        match id {
+            0 => Some(FeatureSet::HalfKP),
+            1 => Some(FeatureSet::HalfKA),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match self {
+            FeatureSet::HalfKP => 0,
+            FeatureSet::HalfKA => 1,
+        }
+    }
+
+    fn piece_kinds(&self) -> usize {
+        match self {
+            FeatureSet::HalfKP => 10,
+            FeatureSet::HalfKA => 12,
+        }
+    }
+
+    pub fn num_features(&self) -> usize {
+        64 * self.piece_kinds() * 64
+    }
+
+    // squares are mirrored vertically for black so both perspectives see their own pieces moving up
+    pub fn index(&self , perspective : Player , king_index : usize , player : Player , piece_type : PieceType , index : usize) -> Option<usize> {
+        if *self == FeatureSet::HalfKP && piece_type == PieceType::King {
+            return None;
+        }
+        let flip = if perspective == Player::White { 0 } else { 56 };
+        let piece_kind = piece_type as usize * 2 + (player != perspective) as usize;
+
+        Some(((king_index ^ flip) * self.piece_kinds() + piece_kind) * 64 + (index ^ flip))
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    pub feature_set : FeatureSet,
+    pub hidden_size : usize,
+    pub feature_weights : Vec<i16>,
+    pub feature_biases : Vec<i16>,
+    pub output_weights : Vec<i16>,
+    pub output_bias : i32,
+}
+
+fn read_u32(bytes : &[u8] , offset : usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_i16s(bytes : &[u8] , offset : usize , count : usize) -> Vec<i16> {
+    bytes[offset..offset + count * 2]
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+impl Network {
+    pub fn load(path : &str) -> Result<Network, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Failed to read network {}: {}", path, e))?;
+        Network::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes : &[u8]) -> Result<Network, String> {
+        if bytes.len() < HEADER_SIZE || bytes[0..4] != NNUE_MAGIC {
+            return Err("Invalid network file: bad magic".to_string());
+        }
+        let version = read_u32(bytes, 4);
+        if version != NNUE_VERSION {
+            return Err(format!("Unsupported network version {}", version));
+        }
+        let feature_set = FeatureSet::from_id(read_u32(bytes, 8))
+            .ok_or_else(|| "Invalid network file: unknown feature set".to_string())?;
+        let hidden_size = read_u32(bytes, 12) as usize;
+        if hidden_size == 0 {
+            return Err("Invalid network file: empty hidden layer".to_string());
+        }
+
+        let features = feature_set.num_features();
+        let expected = HEADER_SIZE + 2 * (features * hidden_size + hidden_size + 2 * hidden_size) + 4;
+        if bytes.len() != expected {
+            return Err(format!("Invalid network file: expected {} bytes, found {}", expected, bytes.len()));
+        }
+
+        let mut offset = HEADER_SIZE;
+        let feature_weights = read_i16s(bytes, offset, features * hidden_size);
+        offset += features * hidden_size * 2;
+        let feature_biases = read_i16s(bytes, offset, hidden_size);
+        offset += hidden_size * 2;
+        let output_weights = read_i16s(bytes, offset, 2 * hidden_size);
+        offset += hidden_size * 4;
+        let output_bias = i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+        Ok(Network { feature_set, hidden_size, feature_weights, feature_biases, output_weights, output_bias })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + 2 * self.feature_weights.len());
+        bytes.extend_from_slice(&NNUE_MAGIC);
+        bytes.extend_from_slice(&NNUE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.feature_set.id().to_le_bytes());
+        bytes.extend_from_slice(&(self.hidden_size as u32).to_le_bytes());
+        for value in self.feature_weights.iter().chain(&self.feature_biases).chain(&self.output_weights) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    pub fn save(&self , path : &str) -> Result<(), String> {
+        fs::write(path, self.to_bytes()).map_err(|e| format!("Failed to write network {}: {}", path, e))
+    }
+
+    fn feature_row(&self , feature : usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden_size..(feature + 1) * self.hidden_size]
+    }
+}
+
+
+// the slices handed to these always have the network's hidden size
+mod simd {
+    use super::QA;
+
+    // the instruction set to run on, detected once when a network is set up
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub enum Simd {
+        Scalar,
+        #[cfg(target_arch = "x86_64")]
+        Avx2,
+    }
+
+    impl Simd {
+        pub fn detect() -> Simd {
+            #[cfg(target_arch = "x86_64")]
+            {
+                if is_x86_feature_detected!("avx2") {
+                    return Simd::Avx2;
+                }
+            }
+            Simd::Scalar
+        }
+
+        pub fn add_row(self , accumulator : &mut [i16] , row : &[i16]) {
+            match self {
+                Simd::Scalar => scalar_add_row(accumulator, row),
+                // SAFETY: `detect` only picks avx2 where the cpu has it
+                #[cfg(target_arch = "x86_64")]
+                Simd::Avx2 => unsafe { avx2::add_row(accumulator, row) },
+            }
+        }
+
+        pub fn sub_row(self , accumulator : &mut [i16] , row : &[i16]) {
+            match self {
+                Simd::Scalar => scalar_sub_row(accumulator, row),
+                // SAFETY: `detect` only picks avx2 where the cpu has it
+                #[cfg(target_arch = "x86_64")]
+                Simd::Avx2 => unsafe { avx2::sub_row(accumulator, row) },
+            }
+        }
+
+        // sum of clamp(accumulator, 0, QA) * weights
+        pub fn crelu_dot(self , accumulator : &[i16] , weights : &[i16]) -> i32 {
+            match self {
+                Simd::Scalar => scalar_crelu_dot(accumulator, weights),
+                // SAFETY: `detect` only picks avx2 where the cpu has it
+                #[cfg(target_arch = "x86_64")]
+                Simd::Avx2 => unsafe { avx2::crelu_dot(accumulator, weights) },
+            }
+        }
+    }
+
+    pub fn scalar_add_row(accumulator : &mut [i16] , row : &[i16]) {
+        for (value, weight) in accumulator.iter_mut().zip(row) {
+            *value = value.wrapping_add(*weight);
+        }
+    }
+
+    pub fn scalar_sub_row(accumulator : &mut [i16] , row : &[i16]) {
+        for (value, weight) in accumulator.iter_mut().zip(row) {
+            *value = value.wrapping_sub(*weight);
+        }
+    }
+
+    pub fn scalar_crelu_dot(accumulator : &[i16] , weights : &[i16]) -> i32 {
+        accumulator.iter().zip(weights).fold(0i32, |sum, (value, weight)| {
+            sum.wrapping_add((*value as i32).clamp(0, QA) * *weight as i32)
+        })
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    mod avx2 {
+        use std::arch::x86_64::*;
+
+        use super::QA;
+
+        const LANES : usize = 16;
+
+        #[target_feature(enable = "avx2")]
+        pub unsafe fn add_row(accumulator : &mut [i16] , row : &[i16]) {
+            let chunks = accumulator.len() / LANES;
+            for chunk in 0..chunks {
+                let offset = chunk * LANES;
+                let a = _mm256_loadu_si256(accumulator.as_ptr().add(offset) as *const __m256i);
+                let b = _mm256_loadu_si256(row.as_ptr().add(offset) as *const __m256i);
+                _mm256_storeu_si256(accumulator.as_mut_ptr().add(offset) as *mut __m256i, _mm256_add_epi16(a, b));
+            }
+            super::scalar_add_row(&mut accumulator[chunks * LANES..], &row[chunks * LANES..]);
+        }
+
+        #[target_feature(enable = "avx2")]
+        pub unsafe fn sub_row(accumulator : &mut [i16] , row : &[i16]) {
+            let chunks = accumulator.len() / LANES;
+            for chunk in 0..chunks {
+                let offset = chunk * LANES;
+                let a = _mm256_loadu_si256(accumulator.as_ptr().add(offset) as *const __m256i);
+                let b = _mm256_loadu_si256(row.as_ptr().add(offset) as *const __m256i);
+                _mm256_storeu_si256(accumulator.as_mut_ptr().add(offset) as *mut __m256i, _mm256_sub_epi16(a, b));
+            }
+            super::scalar_sub_row(&mut accumulator[chunks * LANES..], &row[chunks * LANES..]);
+        }
+
+        #[target_feature(enable = "avx2")]
+        pub unsafe fn crelu_dot(accumulator : &[i16] , weights : &[i16]) -> i32 {
+            let chunks = accumulator.len() / LANES;
+            let zero = _mm256_setzero_si256();
+            let ceiling = _mm256_set1_epi16(QA as i16);
+            let mut sum = _mm256_setzero_si256();
+
+            for chunk in 0..chunks {
+                let offset = chunk * LANES;
+                let a = _mm256_loadu_si256(accumulator.as_ptr().add(offset) as *const __m256i);
+                let w = _mm256_loadu_si256(weights.as_ptr().add(offset) as *const __m256i);
+                let clipped = _mm256_min_epi16(_mm256_max_epi16(a, zero), ceiling);
+                sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, w));
+            }
+
+            let mut lanes = [0i32 ; 8];
+            _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+            let total = lanes.iter().fold(0i32, |total, lane| total.wrapping_add(*lane));
+            total.wrapping_add(super::scalar_crelu_dot(&accumulator[chunks * LANES..], &weights[chunks * LANES..]))
+        }
+    }
+}
+
+pub use simd::{scalar_add_row, scalar_crelu_dot, scalar_sub_row, Simd};
+
+
+// first layer output for both perspectives, indexed by Player
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Accumulator {
+    pub values : [Vec<i16> ; 2],
+    // `piece_hash` of the position the values were computed for
+    pub key : u64,
+}
+
+impl Accumulator {
+    fn new(hidden_size : usize) -> Self {
+        Accumulator { values : [vec![0 ; hidden_size], vec![0 ; hidden_size]], key : 0 }
+    }
+}
+
+// calls `f` with every active feature of the position as seen from `perspective`
+pub fn for_each_feature<F>(feature_set : FeatureSet , board : &BitBoard , perspective : Player , mut f : F)
+where
+    F : FnMut(usize)
+{
+    let king_set = if perspective == Player::White { &board.white_set } else { &board.black_set };
+    let king_index = get_lsb(king_set.kings);
+    if king_index == 64 {
+        return;
+    }
+
+    for (player, pieces) in [(Player::White, &board.white_set), (Player::Black, &board.black_set)] {
+        for piece_type in PIECE_TYPES {
+            let mut positions = piece_bitset(pieces, piece_type);
+            loop {
+                let index = get_lsb(positions);
+                if index == 64 {
+                    break;
+                }
+                if let Some(feature) = feature_set.index(perspective, king_index, player, piece_type, index) {
+                    f(feature);
+                }
+                flip_bit(&mut positions, index);
+            }
+        }
+    }
+}
+
+fn refresh_perspective(network : &Network , simd : Simd , board : &BitBoard , perspective : Player , values : &mut [i16]) {
+    values.copy_from_slice(&network.feature_biases);
+    for_each_feature(network.feature_set, board, perspective, |feature| {
+        simd.add_row(values, network.feature_row(feature));
+    });
+}
+
+const PIECE_TYPES : [PieceType ; 6] = [
+    PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King,
+];
+
+fn piece_bitset(pieces : &PieceSet , piece_type : PieceType) -> u64 {
+    match piece_type {
+        PieceType::Pawn => pieces.pawns,
+        PieceType::Knight => pieces.knights,
+        PieceType::Bishop => pieces.bishops,
+        PieceType::Rook => pieces.rooks,
+        PieceType::Queen => pieces.queens,
+        PieceType::King => pieces.kings,
+    }
+}
+
+
+// a piece appearing on, or disappearing from, a square
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct PieceChange {
+    pub player : Player,
+    pub piece_type : PieceType,
+    pub index : usize,
+}
+
+#[derive(Default)]
+pub struct DirtyPieces {
+    pub removed : Vec<PieceChange>,
+    pub added : Vec<PieceChange>,
+    // set when the side that moved also moved its king
+    pub king_moved : bool,
+}
+
+// the piece changes made by `apply_move`, recovered from the move and what it returned
+pub fn dirty_pieces(turn : Player , mov : u16 , mov_result : &MoveResult) -> DirtyPieces {
+    let src = mov as usize & 0x3F;
+    let dest = (mov as usize >> 6) & 0x3F;
+    let mut dirty = DirtyPieces::default();
+
+    let move_piece = |dirty : &mut DirtyPieces, piece_type : PieceType, from : usize, to : usize| {
+        dirty.removed.push(PieceChange { player : turn, piece_type, index : from });
+        dirty.added.push(PieceChange { player : turn, piece_type, index : to });
+    };
+
+    match mov_result {
+        MoveResult::NormalMove(src_piece_type, dest_piece_type, ..) => {
+            move_piece(&mut dirty, *src_piece_type, src, dest);
+            if let Some(captured) = dest_piece_type {
+                dirty.removed.push(PieceChange { player : !turn, piece_type : *captured, index : dest });
+            }
+            dirty.king_moved = *src_piece_type == PieceType::King;
+        }
+        MoveResult::Promotions(promoted, dest_piece_type, ..) => {
+            dirty.removed.push(PieceChange { player : turn, piece_type : PieceType::Pawn, index : src });
+            dirty.added.push(PieceChange { player : turn, piece_type : *promoted, index : dest });
+            if let Some(captured) = dest_piece_type {
+                dirty.removed.push(PieceChange { player : !turn, piece_type : *captured, index : dest });
+            }
+        }
+        MoveResult::Enpassant(..) => {
+            move_piece(&mut dirty, PieceType::Pawn, src, dest);
+            let captured = if turn == Player::White { dest - 8 } else { dest + 8 };
+            dirty.removed.push(PieceChange { player : !turn, piece_type : PieceType::Pawn, index : captured });
+        }
+        MoveResult::DoublePawnPush(..) => {
+            move_piece(&mut dirty, PieceType::Pawn, src, dest);
+        }
+        MoveResult::Castle(..) => {
+            // `dest` is the rook's square, castling is encoded as the king taking it
+            let (king_dest, rook_dest) = castle_destinations(src, mov >> 12 == CASTLE_KING);
+            move_piece(&mut dirty, PieceType::King, src, king_dest);
+            move_piece(&mut dirty, PieceType::Rook, dest, rook_dest);
+            dirty.king_moved = true;
+        }
+    }
+
+    dirty
+}
+
+
+// plies of the board's history `sync` will replay before giving up and refreshing
+const MAX_CATCH_UP : usize = 16;
+
+// a network plus the accumulators for the current line of play
+pub struct NnueState {
+    pub network : Network,
+    simd : Simd,
+    stack : Vec<Accumulator>,
+    depth : usize,
+}
+
+impl NnueState {
+    pub fn new(network : Network , board : &BitBoard) -> Self {
+        let mut state = NnueState {
+            stack : vec![Accumulator::new(network.hidden_size)],
+            network,
+            simd : Simd::detect(),
+            depth : 0,
+        };
+        state.refresh(board);
+        state
+    }
+
+    pub fn load(path : &str , board : &BitBoard) -> Result<Self, String> {
+        Ok(NnueState::new(Network::load(path)?, board))
+    }
+
+    // rebuilds the accumulator of the current ply from scratch
+    pub fn refresh(&mut self, board : &BitBoard) {
+        let accumulator = &mut self.stack[self.depth];
+        for perspective in [Player::White, Player::Black] {
+            refresh_perspective(&self.network, self.simd, board, perspective, &mut accumulator.values[perspective as usize]);
+        }
+        accumulator.key = board.piece_hash;
+    }
+
+    // resets to a single ply for a new root position
+    pub fn set_position(&mut self, board : &BitBoard) {
+        self.depth = 0;
+        self.refresh(board);
+    }
+
+    pub fn accumulator(&self) -> &Accumulator {
+        &self.stack[self.depth]
+    }
+
+    // `board` is the position after `mov` has been applied
+    pub fn push(&mut self, board : &BitBoard , turn : Player , mov : u16 , mov_result : &MoveResult) {
+        self.push_with_key(board, turn, mov, mov_result, board.piece_hash);
+    }
+
+    // `key` is the `piece_hash` right after `mov`; the king squares are read from `board`,
+    // so no king may have moved since
+    fn push_with_key(&mut self, board : &BitBoard , turn : Player , mov : u16 , mov_result : &MoveResult , key : u64) {
+        let dirty = dirty_pieces(turn, mov, mov_result);
+
+        if self.stack.len() == self.depth + 1 {
+            self.stack.push(Accumulator::new(self.network.hidden_size));
+        }
+        let (parents, children) = self.stack.split_at_mut(self.depth + 1);
+        let parent = &parents[self.depth];
+        let child = &mut children[0];
+        self.depth += 1;
+        child.key = key;
+
+        for perspective in [Player::White, Player::Black] {
+            let values = &mut child.values[perspective as usize];
+
+            // every feature of the mover's perspective depends on its king square
+            if dirty.king_moved && perspective == turn {
+                refresh_perspective(&self.network, self.simd, board, perspective, values);
+                continue;
+            }
+
+            values.copy_from_slice(&parent.values[perspective as usize]);
+            let king_set = if perspective == Player::White { &board.white_set } else { &board.black_set };
+            let king_index = get_lsb(king_set.kings);
+
+            for change in &dirty.removed {
+                if let Some(feature) = self.network.feature_set.index(perspective, king_index, change.player, change.piece_type, change.index) {
+                    self.simd.sub_row(values, self.network.feature_row(feature));
+                }
+            }
+            for change in &dirty.added {
+                if let Some(feature) = self.network.feature_set.index(perspective, king_index, change.player, change.piece_type, change.index) {
+                    self.simd.add_row(values, self.network.feature_row(feature));
+                }
+            }
+        }
+    }
+
+    pub fn pop(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    // brings the accumulators in line with `board`, whichever way it was moved: plies taken back are
+    // dropped, plies played since with `make_move` are replayed from the board's history, and anything
+    // else, or a king move before the last ply, is refreshed from scratch
+    pub fn sync(&mut self, board : &BitBoard) {
+        if let Some(depth) = self.stack[..=self.depth].iter().rposition(|accumulator| accumulator.key == board.piece_hash) {
+            self.depth = depth;
+            return;
+        }
+
+        // piece hashes of the positions before the last plies, newest first
+        let recent = &board.history[board.history.len().saturating_sub(MAX_CATCH_UP)..];
+        let mut keys = Vec::with_capacity(recent.len());
+        let (mut key, mut turn) = (board.piece_hash, !board.player);
+        for state in recent.iter().rev() {
+            key ^= piece_key_change(turn, state.mov, &state.result);
+            keys.push(key);
+            turn = !turn;
+        }
+
+        for depth in (0..=self.depth).rev() {
+            let Some(plies) = keys.iter().position(|key| *key == self.stack[depth].key).map(|found| found + 1) else {
+                continue;
+            };
+            let replay = &recent[recent.len() - plies..];
+            let king_moved = replay[..plies - 1].iter().any(|state| {
+                matches!(state.result, MoveResult::Castle(..) | MoveResult::NormalMove(PieceType::King, ..))
+            });
+            if king_moved {
+                break;
+            }
+
+            self.depth = depth;
+            let mut turn = if plies % 2 == 1 { !board.player } else { board.player };
+            for (ply, state) in replay.iter().enumerate() {
+                let key = if ply + 1 == plies { board.piece_hash } else { keys[plies - ply - 2] };
+                self.push_with_key(board, turn, state.mov, &state.result, key);
+                turn = !turn;
+            }
+            return;
+        }
+        self.refresh(board);
+    }
+
+    // applies the move to the board and updates the accumulator along with it
+    pub fn apply_move(&mut self, board : &mut BitBoard , turn : Player , mov : u16) -> MoveResult {
+        let mov_result = board.apply_move(turn, mov);
+        self.push(board, turn, mov, &mov_result);
+        mov_result
+    }
+
+    pub fn unapply_move(&mut self, board : &mut BitBoard , turn : Player , mov : u16 , mov_result : MoveResult) {
+        board.unapply_move(turn, mov, mov_result);
+        self.pop();
+    }
+
+    // score in centipawns from the point of view of `board.player`, after catching the accumulators up with `board`
+    pub fn evaluate(&mut self, board : &BitBoard) -> i32 {
+        self.sync(board);
+        let accumulator = self.accumulator();
+        let hidden = self.network.hidden_size;
+        let us = board.player as usize;
+        let them = !board.player as usize;
+
+        let output = self.simd.crelu_dot(&accumulator.values[us], &self.network.output_weights[..hidden])
+            .wrapping_add(self.simd.crelu_dot(&accumulator.values[them], &self.network.output_weights[hidden..]))
+            .wrapping_add(self.network.output_bias);
+
+        (output as i64 * OUTPUT_SCALE as i64 / (QA * QB) as i64) as i32
+    }
+}
@@ -0,0 +1,366 @@
+use std::{fs, thread};
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{bit_board::BitBoard, player::Player};
+
+use super::dataset::{load_lines, parse_result};
+use super::nnue::{for_each_feature, FeatureSet, Network, OUTPUT_SCALE, QA, QB};
+
+
+// scores are squashed with sigmoid(score / SIGMOID_SCALE) before being compared to results
+pub const SIGMOID_SCALE : f32 = 400.0;
+// keeps every quantized weight well inside its integer type
+const WEIGHT_CLIP : f32 = 1.98;
+
+const ADAM_BETA1 : f32 = 0.9;
+const ADAM_BETA2 : f32 = 0.999;
+const ADAM_EPSILON : f32 = 1e-8;
+
+// checkpoint layout, all little endian:
+//   magic "RCNT", version u32, feature set u32, hidden size u32, adam step u64, epoch u32,
+//   then parameters, first moments and second moments as f32 [parameter count] each
+const CHECKPOINT_MAGIC : [u8 ; 4] = *b"RCNT";
+const CHECKPOINT_VERSION : u32 = 1;
+const CHECKPOINT_HEADER_SIZE : usize = 28;
+
+
+pub struct TrainingPosition {
+    // active features from the side to move, then from the other side
+    pub features : [Vec<u16> ; 2],
+    // both from the side to move: centipawns, and 1 / 0.5 / 0 for a win / draw / loss
+    pub score : f32,
+    pub result : f32,
+}
+
+impl TrainingPosition {
+    pub fn from_board(board : &BitBoard , feature_set : FeatureSet , white_score : f32 , white_result : f32) -> Self {
+        let us = board.player;
+        let features_for = |perspective : Player| {
+            let mut features = Vec::with_capacity(32);
+            for_each_feature(feature_set, board, perspective, |feature| features.push(feature as u16));
+            features
+        };
+
+        let (score, result) = if us == Player::White {
+            (white_score, white_result)
+        } else {
+            (-white_score, 1.0 - white_result)
+        };
+
+        TrainingPosition { features : [features_for(us), features_for(!us)], score, result }
+    }
+
+    // "<fen> | <score> | <result>", with the score in centipawns and the result given for white
+    pub fn parse(line : &str , feature_set : FeatureSet) -> Result<Self, String> {
+        let fields : Vec<&str> = line.split('|').map(str::trim).collect();
+        if fields.len() != 3 {
+            return Err(format!("Expected \"fen | score | result\", found \"{}\"", line));
+        }
+
+        let board = BitBoard::fen_to_valid_bitboard(fields[0])?;
+        let score = fields[1].parse::<f32>().map_err(|_| format!("Invalid score {}", fields[1]))?;
+        let result = parse_result(fields[2]).ok_or_else(|| format!("Invalid result {}", fields[2]))? as f32;
+
+        Ok(TrainingPosition::from_board(&board, feature_set, score, result))
+    }
+}
+
+// skips blank lines and lines starting with '#'
+pub fn load_positions(path : &str , feature_set : FeatureSet) -> Result<Vec<TrainingPosition>, String> {
+    load_lines(path, |line| TrainingPosition::parse(line, feature_set))
+}
+
+
+fn sigmoid(value : f32) -> f32 {
+    1.0 / (1.0 + (-value).exp())
+}
+
+// the float twin of `nnue::Network`, with every parameter in one flat vector:
+// feature weights, feature biases, output weights, output bias
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatNetwork {
+    pub feature_set : FeatureSet,
+    pub hidden_size : usize,
+    pub params : Vec<f32>,
+}
+
+impl FloatNetwork {
+    pub fn new<R : Rng>(feature_set : FeatureSet , hidden_size : usize , rng : &mut R) -> Self {
+        let features = feature_set.num_features();
+        // roughly thirty features are active at once, and the output sees 2 * hidden_size inputs
+        let feature_range = 1.0 / 32f32.sqrt();
+        let output_range = 1.0 / ((2 * hidden_size) as f32).sqrt();
+
+        let mut params = Vec::with_capacity((features + 3) * hidden_size + 1);
+        params.extend((0..features * hidden_size).map(|_| rng.random_range(-feature_range..feature_range)));
+        params.extend((0..hidden_size).map(|_| 0.1));
+        params.extend((0..2 * hidden_size).map(|_| rng.random_range(-output_range..output_range)));
+        params.push(0.0);
+
+        FloatNetwork { feature_set, hidden_size, params }
+    }
+
+    fn feature_bias_offset(&self) -> usize {
+        self.feature_set.num_features() * self.hidden_size
+    }
+
+    fn output_weight_offset(&self) -> usize {
+        self.feature_bias_offset() + self.hidden_size
+    }
+
+    fn output_bias_offset(&self) -> usize {
+        self.output_weight_offset() + 2 * self.hidden_size
+    }
+
+    fn accumulate(&self, features : &[u16] , accumulator : &mut [f32]) {
+        let hidden = self.hidden_size;
+        let bias_offset = self.feature_bias_offset();
+        accumulator.copy_from_slice(&self.params[bias_offset..bias_offset + hidden]);
+        for feature in features {
+            let row = &self.params[*feature as usize * hidden..(*feature as usize + 1) * hidden];
+            for (value, weight) in accumulator.iter_mut().zip(row) {
+                *value += weight;
+            }
+        }
+    }
+
+    fn output(&self, accumulators : &[Vec<f32> ; 2]) -> f32 {
+        let output_weights = &self.params[self.output_weight_offset()..self.output_bias_offset()];
+        let inputs = accumulators[0].iter().chain(&accumulators[1]);
+
+        inputs.zip(output_weights)
+            .fold(self.params[self.output_bias_offset()], |sum, (value, weight)| sum + value.clamp(0.0, 1.0) * weight)
+    }
+
+    // predicted score in centipawns from the side to move, what the quantized network approximates
+    pub fn evaluate(&self, position : &TrainingPosition) -> f32 {
+        let mut accumulators = [vec![0.0 ; self.hidden_size], vec![0.0 ; self.hidden_size]];
+        self.accumulate(&position.features[0], &mut accumulators[0]);
+        self.accumulate(&position.features[1], &mut accumulators[1]);
+        self.output(&accumulators) * OUTPUT_SCALE as f32
+    }
+
+    pub fn quantize(&self) -> Network {
+        let quantize = |values : &[f32], scale : i32| -> Vec<i16> {
+            values.iter().map(|value| (value * scale as f32).round() as i16).collect()
+        };
+
+        Network {
+            feature_set : self.feature_set,
+            hidden_size : self.hidden_size,
+            feature_weights : quantize(&self.params[..self.feature_bias_offset()], QA),
+            feature_biases : quantize(&self.params[self.feature_bias_offset()..self.output_weight_offset()], QA),
+            output_weights : quantize(&self.params[self.output_weight_offset()..self.output_bias_offset()], QB),
+            output_bias : (self.params[self.output_bias_offset()] * (QA * QB) as f32).round() as i32,
+        }
+    }
+
+    // adds the gradient of the loss for one position to `gradients` and returns the loss
+    fn backpropagate(&self, position : &TrainingPosition , lambda : f32 , accumulators : &mut [Vec<f32> ; 2] , gradients : &mut [f32]) -> f32 {
+        let hidden = self.hidden_size;
+        self.accumulate(&position.features[0], &mut accumulators[0]);
+        self.accumulate(&position.features[1], &mut accumulators[1]);
+
+        let scale = OUTPUT_SCALE as f32 / SIGMOID_SCALE;
+        let prediction = sigmoid(self.output(accumulators) * scale);
+        let target = lambda * sigmoid(position.score / SIGMOID_SCALE) + (1.0 - lambda) * position.result;
+        let error = prediction - target;
+        let output_gradient = 2.0 * error * prediction * (1.0 - prediction) * scale;
+
+        let bias_offset = self.feature_bias_offset();
+        let output_weight_offset = self.output_weight_offset();
+        gradients[self.output_bias_offset()] += output_gradient;
+
+        for (side, accumulator) in accumulators.iter().enumerate() {
+            let weight_offset = output_weight_offset + side * hidden;
+            for (i, value) in accumulator.iter().enumerate() {
+                gradients[weight_offset + i] += output_gradient * value.clamp(0.0, 1.0);
+            }
+
+            for (i, value) in accumulator.iter().enumerate() {
+                // clipped relu passes the gradient through only inside (0, 1)
+                if *value <= 0.0 || *value >= 1.0 {
+                    continue;
+                }
+                let gradient = output_gradient * self.params[weight_offset + i];
+                gradients[bias_offset + i] += gradient;
+                for feature in &position.features[side] {
+                    gradients[*feature as usize * hidden + i] += gradient;
+                }
+            }
+        }
+
+        error * error
+    }
+}
+
+
+#[derive(Clone, Copy, Debug)]
+pub struct TrainerConfig {
+    pub learning_rate : f32,
+    // weight of the engine score in the target, the rest goes to the game result
+    pub lambda : f32,
+    pub batch_size : usize,
+    pub threads : usize,
+}
+
+impl Default for TrainerConfig {
+    fn default() -> Self {
+        TrainerConfig { learning_rate : 0.001, lambda : 0.75, batch_size : 16384, threads : 1 }
+    }
+}
+
+pub struct Trainer {
+    pub network : FloatNetwork,
+    pub config : TrainerConfig,
+    pub epoch : u32,
+    step : u64,
+    first_moments : Vec<f32>,
+    second_moments : Vec<f32>,
+    // one gradient buffer per thread
+    gradients : Vec<Vec<f32>>,
+}
+
+impl Trainer {
+    pub fn new(network : FloatNetwork , config : TrainerConfig) -> Self {
+        let size = network.params.len();
+        Trainer {
+            network,
+            config,
+            epoch : 0,
+            step : 0,
+            first_moments : vec![0.0 ; size],
+            second_moments : vec![0.0 ; size],
+            gradients : vec![vec![0.0 ; size] ; config.threads.max(1)],
+        }
+    }
+
+    // one Adam step over the batch, returns the mean loss before the step
+    pub fn train_batch(&mut self, batch : &[&TrainingPosition]) -> f32 {
+        if batch.is_empty() {
+            return 0.0;
+        }
+
+        let network = &self.network;
+        let lambda = self.config.lambda;
+        let chunk_size = batch.len().div_ceil(self.gradients.len());
+
+        let total_loss : f32 = thread::scope(|scope| {
+            let workers : Vec<_> = self.gradients.iter_mut()
+                .zip(batch.chunks(chunk_size))
+                .map(|(gradients, chunk)| {
+                    scope.spawn(move || {
+                        gradients.fill(0.0);
+                        let mut accumulators = [vec![0.0 ; network.hidden_size], vec![0.0 ; network.hidden_size]];
+                        chunk.iter()
+                            .map(|position| network.backpropagate(position, lambda, &mut accumulators, gradients))
+                            .sum::<f32>()
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|worker| worker.join().unwrap()).sum()
+        });
+
+        let used = batch.len().div_ceil(chunk_size);
+        let (first, rest) = self.gradients.split_at_mut(1);
+        for other in &rest[..used - 1] {
+            for (sum, gradient) in first[0].iter_mut().zip(other.iter()) {
+                *sum += gradient;
+            }
+        }
+
+        self.step += 1;
+        let scale = 1.0 / batch.len() as f32;
+        let step_size = self.config.learning_rate * (1.0 - ADAM_BETA2.powi(self.step as i32)).sqrt()
+            / (1.0 - ADAM_BETA1.powi(self.step as i32));
+
+        for (((param, gradient), m), v) in self.network.params.iter_mut()
+            .zip(&first[0])
+            .zip(&mut self.first_moments)
+            .zip(&mut self.second_moments)
+        {
+            let gradient = gradient * scale;
+            *m = ADAM_BETA1 * *m + (1.0 - ADAM_BETA1) * gradient;
+            *v = ADAM_BETA2 * *v + (1.0 - ADAM_BETA2) * gradient * gradient;
+            *param = (*param - step_size * *m / (v.sqrt() + ADAM_EPSILON)).clamp(-WEIGHT_CLIP, WEIGHT_CLIP);
+        }
+
+        total_loss / batch.len() as f32
+    }
+
+    // shuffles the positions and runs one pass over them, returns the mean loss
+    pub fn train_epoch<R : Rng>(&mut self, positions : &[TrainingPosition] , rng : &mut R) -> f32 {
+        let mut order : Vec<usize> = (0..positions.len()).collect();
+        order.shuffle(rng);
+
+        let mut total_loss = 0.0;
+        for chunk in order.chunks(self.config.batch_size.max(1)) {
+            let batch : Vec<&TrainingPosition> = chunk.iter().map(|index| &positions[*index]).collect();
+            total_loss += self.train_batch(&batch) * batch.len() as f32;
+        }
+
+        self.epoch += 1;
+        if positions.is_empty() { 0.0 } else { total_loss / positions.len() as f32 }
+    }
+
+    pub fn loss(&self, positions : &[TrainingPosition]) -> f32 {
+        if positions.is_empty() {
+            return 0.0;
+        }
+        let lambda = self.config.lambda;
+        let total : f32 = positions.iter().map(|position| {
+            let prediction = sigmoid(self.network.evaluate(position) / SIGMOID_SCALE);
+            let target = lambda * sigmoid(position.score / SIGMOID_SCALE) + (1.0 - lambda) * position.result;
+            (prediction - target) * (prediction - target)
+        }).sum();
+        total / positions.len() as f32
+    }
+
+    pub fn save_checkpoint(&self, path : &str) -> Result<(), String> {
+        let mut bytes = Vec::with_capacity(CHECKPOINT_HEADER_SIZE + 12 * self.network.params.len());
+        bytes.extend_from_slice(&CHECKPOINT_MAGIC);
+        bytes.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.network.feature_set.id().to_le_bytes());
+        bytes.extend_from_slice(&(self.network.hidden_size as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.step.to_le_bytes());
+        bytes.extend_from_slice(&self.epoch.to_le_bytes());
+        for value in self.network.params.iter().chain(&self.first_moments).chain(&self.second_moments) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        fs::write(path, bytes).map_err(|e| format!("Failed to write checkpoint {}: {}", path, e))
+    }
+
+    pub fn load_checkpoint(path : &str , config : TrainerConfig) -> Result<Trainer, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Failed to read checkpoint {}: {}", path, e))?;
+        if bytes.len() < CHECKPOINT_HEADER_SIZE || bytes[0..4] != CHECKPOINT_MAGIC {
+            return Err("Invalid checkpoint: bad magic".to_string());
+        }
+        let read_u32 = |offset : usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+        if read_u32(4) != CHECKPOINT_VERSION {
+            return Err(format!("Unsupported checkpoint version {}", read_u32(4)));
+        }
+        let feature_set = FeatureSet::from_id(read_u32(8))
+            .ok_or_else(|| "Invalid checkpoint: unknown feature set".to_string())?;
+        let hidden_size = read_u32(12) as usize;
+        let step = u64::from_le_bytes(bytes[16..24].try_into().unwrap());
+        let epoch = read_u32(24);
+
+        let size = (feature_set.num_features() + 3) * hidden_size + 1;
+        if bytes.len() != CHECKPOINT_HEADER_SIZE + 12 * size {
+            return Err("Invalid checkpoint: unexpected size".to_string());
+        }
+        let mut values = bytes[CHECKPOINT_HEADER_SIZE..]
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()));
+
+        let params = values.by_ref().take(size).collect();
+        let mut trainer = Trainer::new(FloatNetwork { feature_set, hidden_size, params }, config);
+        trainer.first_moments = values.by_ref().take(size).collect();
+        trainer.second_moments = values.collect();
+        trainer.step = step;
+        trainer.epoch = epoch;
+        Ok(trainer)
+    }
+}
@@ -0,0 +1,243 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use super::king_safety::*;
+use super::material::{BISHOP_PAIR, KNIGHT_PAWN_ADJUSTMENT, REDUNDANT_QUEEN, REDUNDANT_ROOK,
+SCALE_OPPOSITE_BISHOPS, SCALE_OPPOSITE_BISHOPS_WITH_PIECES, SCALE_PAWNLESS_EXCHANGE_UP};
+use super::mobility::*;
+use super::pawns::*;
+use super::*;
+
+
+// anything the tuner can read and write as a run of integers
+pub trait Parameter {
+    fn for_each_value(&mut self, f : &mut dyn FnMut(&mut i32));
+    fn rust_type(&self) -> String;
+    fn rust_value(&self) -> String;
+}
+
+impl Parameter for i32 {
+    fn for_each_value(&mut self, f : &mut dyn FnMut(&mut i32)) {
+        f(self);
+    }
+
+    fn rust_type(&self) -> String {
+        "i32".to_string()
+    }
+
+    fn rust_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Parameter for Score {
+    fn for_each_value(&mut self, f : &mut dyn FnMut(&mut i32)) {
+        f(&mut self.mg);
+        f(&mut self.eg);
+    }
+
+    fn rust_type(&self) -> String {
+        "Score".to_string()
+    }
+
+    fn rust_value(&self) -> String {
+        format!("Score::new({}, {})", self.mg, self.eg)
+    }
+}
+
+impl<const N : usize> Parameter for [i32 ; N] {
+    fn for_each_value(&mut self, f : &mut dyn FnMut(&mut i32)) {
+        self.iter_mut().for_each(f);
+    }
+
+    fn rust_type(&self) -> String {
+        format!("[i32 ; {}]", N)
+    }
+
+    // eight to a row, so square tables keep their board layout
+    fn rust_value(&self) -> String {
+        let rows : Vec<String> = self.chunks(8)
+            .map(|row| format!("   {},", row.iter().map(|value| format!("{:>3}", value)).collect::<Vec<_>>().join(", ")))
+            .collect();
+        format!("[\n{}\n]", rows.join("\n"))
+    }
+}
+
+impl<const N : usize> Parameter for [Score ; N] {
+    fn for_each_value(&mut self, f : &mut dyn FnMut(&mut i32)) {
+        self.iter_mut().for_each(|score| score.for_each_value(f));
+    }
+
+    fn rust_type(&self) -> String {
+        format!("[Score ; {}]", N)
+    }
+
+    fn rust_value(&self) -> String {
+        let rows : Vec<String> = self.chunks(4)
+            .map(|row| format!("    {}", row.iter().map(|score| score.rust_value()).collect::<Vec<_>>().join(", ")))
+            .collect();
+        format!("[\n{},\n]", rows.join(",\n"))
+    }
+}
+
+
+// serde only derives arrays up to 32 elements
+mod big_array {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S : Serializer, T : Serialize, const N : usize>(values : &[T ; N] , serializer : S) -> Result<S::Ok, S::Error> {
+        values.as_slice().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D : Deserializer<'de>, T : Deserialize<'de>, const N : usize>(deserializer : D) -> Result<[T ; N], D::Error> {
+        let values = Vec::<T>::deserialize(deserializer)?;
+        let length = values.len();
+        values.try_into().map_err(|_| D::Error::invalid_length(length, &format!("{} values", N).as_str()))
+    }
+}
+
+
+macro_rules! eval_params {
+    ($($(#[$attribute:meta])* $name:ident : $type:ty = $default:expr,)*) => {
+        // every weight the evaluation uses; the defaults are the constants in the evaluation modules,
+        // and a JSON file only needs the fields it changes
+        #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct EvalParams {
+            $($(#[$attribute])* pub $name : $type,)*
+        }
+
+        pub const DEFAULT_PARAMS : EvalParams = EvalParams {
+            $($name : $default,)*
+        };
+
+        impl EvalParams {
+            // calls `f` with every parameter and its name, always in the same order
+            pub fn visit(&mut self, f : &mut dyn FnMut(&'static str, &mut dyn Parameter)) {
+                $(f(stringify!($name), &mut self.$name);)*
+            }
+        }
+    };
+}
+
+eval_params! {
+    pawn_value : Score = PAWN_VALUE,
+    knight_value : Score = KNIGHT_VALUE,
+    bishop_value : Score = BISHOP_VALUE,
+    rook_value : Score = ROOK_VALUE,
+    queen_value : Score = QUEEN_VALUE,
+    tempo : Score = TEMPO,
+
+    #[serde(with = "big_array")]
+    pawn_table : [i32 ; 64] = PAWN_TABLE,
+    #[serde(with = "big_array")]
+    knight_table : [i32 ; 64] = KNIGHT_TABLE,
+    #[serde(with = "big_array")]
+    bishop_table : [i32 ; 64] = BISHOP_TABLE,
+    #[serde(with = "big_array")]
+    rook_table : [i32 ; 64] = ROOK_TABLE,
+    #[serde(with = "big_array")]
+    queen_table : [i32 ; 64] = QUEEN_TABLE,
+    #[serde(with = "big_array")]
+    king_mg_table : [i32 ; 64] = KING_MG_TABLE,
+    #[serde(with = "big_array")]
+    king_eg_table : [i32 ; 64] = KING_EG_TABLE,
+
+    isolated_pawn : Score = ISOLATED_PAWN,
+    doubled_pawn : Score = DOUBLED_PAWN,
+    backward_pawn : Score = BACKWARD_PAWN,
+    pawn_island : Score = PAWN_ISLAND,
+    connected_pawn : [Score ; 8] = CONNECTED_PAWN,
+    candidate_pawn : [Score ; 8] = CANDIDATE_PAWN,
+    passed_pawn : [Score ; 8] = PASSED_PAWN,
+    blocked_passed_pawn : [Score ; 8] = BLOCKED_PASSED_PAWN,
+
+    knight_attacker_weight : i32 = KNIGHT_ATTACKER_WEIGHT,
+    bishop_attacker_weight : i32 = BISHOP_ATTACKER_WEIGHT,
+    rook_attacker_weight : i32 = ROOK_ATTACKER_WEIGHT,
+    queen_attacker_weight : i32 = QUEEN_ATTACKER_WEIGHT,
+    zone_attack_weight : i32 = ZONE_ATTACK_WEIGHT,
+    knight_safe_check : i32 = KNIGHT_SAFE_CHECK,
+    bishop_safe_check : i32 = BISHOP_SAFE_CHECK,
+    rook_safe_check : i32 = ROOK_SAFE_CHECK,
+    queen_safe_check : i32 = QUEEN_SAFE_CHECK,
+    no_queen_danger : i32 = NO_QUEEN_DANGER,
+    pawn_shield : [Score ; 8] = PAWN_SHIELD,
+    pawn_storm : [Score ; 8] = PAWN_STORM,
+    blocked_pawn_storm : [Score ; 8] = BLOCKED_PAWN_STORM,
+    semi_open_file_near_king : Score = SEMI_OPEN_FILE_NEAR_KING,
+    open_file_near_king : Score = OPEN_FILE_NEAR_KING,
+
+    knight_mobility : [Score ; 9] = KNIGHT_MOBILITY,
+    bishop_mobility : [Score ; 14] = BISHOP_MOBILITY,
+    rook_mobility : [Score ; 15] = ROOK_MOBILITY,
+    queen_mobility : [Score ; 28] = QUEEN_MOBILITY,
+    rook_open_file : Score = ROOK_OPEN_FILE,
+    rook_semi_open_file : Score = ROOK_SEMI_OPEN_FILE,
+    rook_on_seventh : Score = ROOK_ON_SEVENTH,
+    knight_outpost : Score = KNIGHT_OUTPOST,
+    bishop_outpost : Score = BISHOP_OUTPOST,
+    bad_bishop_pawn : Score = BAD_BISHOP_PAWN,
+    trapped_rook : Score = TRAPPED_ROOK,
+    trapped_bishop : Score = TRAPPED_BISHOP,
+
+    bishop_pair : Score = BISHOP_PAIR,
+    redundant_rook : Score = REDUNDANT_ROOK,
+    redundant_queen : Score = REDUNDANT_QUEEN,
+    knight_pawn_adjustment : Score = KNIGHT_PAWN_ADJUSTMENT,
+    scale_opposite_bishops : i32 = SCALE_OPPOSITE_BISHOPS,
+    scale_opposite_bishops_with_pieces : i32 = SCALE_OPPOSITE_BISHOPS_WITH_PIECES,
+    scale_pawnless_exchange_up : i32 = SCALE_PAWNLESS_EXCHANGE_UP,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        DEFAULT_PARAMS
+    }
+}
+
+impl EvalParams {
+    // every integer weight, in `visit` order
+    pub fn to_vector(&self) -> Vec<i32> {
+        let mut values = Vec::new();
+        self.clone().visit(&mut |_, parameter| parameter.for_each_value(&mut |value| values.push(*value)));
+        values
+    }
+
+    pub fn set_from_vector(&mut self, values : &[i32]) {
+        let mut values = values.iter();
+        self.visit(&mut |_, parameter| parameter.for_each_value(&mut |value| {
+            *value = *values.next().expect("parameter vector is too short");
+        }));
+    }
+
+    pub fn load(path : &str) -> Result<EvalParams, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        EvalParams::from_json(&json).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn from_json(json : &str) -> Result<EvalParams, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid evaluation parameters: {}", e))
+    }
+
+    pub fn save(&self , path : &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize parameters: {}", e))?;
+        fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+
+    pub fn from_vector(values : &[i32]) -> Self {
+        let mut params = DEFAULT_PARAMS;
+        params.set_from_vector(values);
+        params
+    }
+
+    // one constant per parameter, named and laid out like the ones in the evaluation modules
+    pub fn to_rust_source(&self) -> String {
+        let mut source = String::new();
+        self.clone().visit(&mut |name, parameter| {
+            source += &format!("pub const {} : {} = {};\n", name.to_uppercase(), parameter.rust_type(), parameter.rust_value());
+        });
+        source
+    }
+}
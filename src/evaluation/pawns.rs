@@ -0,0 +1,229 @@
+use crate::{bit_board::BitBoard, player::Player,
+utils::{file_fill, flip_bit, get_lsb, north_fill, shift_east, shift_west, south_fill, RANK_1}};
+
+use super::{params::EvalParams, relative_rank, Score};
+
+
+pub const ISOLATED_PAWN : Score = Score::new(-10, -15);
+pub const DOUBLED_PAWN : Score = Score::new(-10, -25);
+pub const BACKWARD_PAWN : Score = Score::new(-8, -12);
+pub const PAWN_ISLAND : Score = Score::new(-5, -10);
+
+// indexed by the rank of the pawn relative to its owner
+pub const CONNECTED_PAWN : [Score ; 8] = [
+    Score::new(0, 0), Score::new(2, 2), Score::new(4, 4), Score::new(6, 8),
+    Score::new(12, 16), Score::new(20, 30), Score::new(35, 50), Score::new(0, 0),
+];
+
+pub const CANDIDATE_PAWN : [Score ; 8] = [
+    Score::new(0, 0), Score::new(2, 4), Score::new(3, 6), Score::new(5, 10),
+    Score::new(10, 20), Score::new(20, 35), Score::new(0, 0), Score::new(0, 0),
+];
+
+pub const PASSED_PAWN : [Score ; 8] = [
+    Score::new(0, 0), Score::new(5, 10), Score::new(10, 15), Score::new(15, 25),
+    Score::new(30, 50), Score::new(50, 90), Score::new(80, 140), Score::new(0, 0),
+];
+
+// used instead of PASSED_PAWN when an enemy piece sits on the stop square
+pub const BLOCKED_PASSED_PAWN : [Score ; 8] = [
+    Score::new(0, 0), Score::new(3, 5), Score::new(5, 8), Score::new(8, 12),
+    Score::new(15, 25), Score::new(25, 45), Score::new(40, 70), Score::new(0, 0),
+];
+
+
+pub fn push(bitset : u64 , player : Player) -> u64 {
+    if player == Player::White { bitset << 8 } else { bitset >> 8 }
+}
+
+pub fn forward_fill(bitset : u64 , player : Player) -> u64 {
+    if player == Player::White { north_fill(bitset) } else { south_fill(bitset) }
+}
+
+// squares strictly in front of the given pawns, on their own files
+pub fn front_span(pawns : u64 , player : Player) -> u64 {
+    forward_fill(push(pawns, player), player)
+}
+
+// squares strictly behind the given pawns, on their own files
+pub fn rear_span(pawns : u64 , player : Player) -> u64 {
+    forward_fill(push(pawns, !player), !player)
+}
+
+pub fn pawn_attacks(pawns : u64 , player : Player) -> u64 {
+    let pushed = push(pawns, player);
+    shift_east(pushed) | shift_west(pushed)
+}
+
+pub fn adjacent_files(bitset : u64) -> u64 {
+    let files = file_fill(bitset);
+    shift_east(files) | shift_west(files)
+}
+
+
+// no enemy pawn can stop or capture it; the rear pawn of a doubled pair does not count
+pub fn passed_pawns(ours : u64 , theirs : u64 , player : Player) -> u64 {
+    let their_span = front_span(theirs, !player);
+    ours & !(their_span | shift_east(their_span) | shift_west(their_span)) & !rear_span(ours, player)
+}
+
+pub fn isolated_pawns(ours : u64) -> u64 {
+    ours & !adjacent_files(ours)
+}
+
+// every pawn that has another friendly pawn behind it on the same file
+pub fn doubled_pawns(ours : u64 , player : Player) -> u64 {
+    ours & front_span(ours, player)
+}
+
+// the stop square is controlled by an enemy pawn and no friendly pawn can ever defend it
+pub fn backward_pawns(ours : u64 , theirs : u64 , player : Player) -> u64 {
+    let attack_span = forward_fill(pawn_attacks(ours, player), player);
+    let stops = push(ours, player) & pawn_attacks(theirs, !player) & !attack_span;
+    push(stops, !player) & !isolated_pawns(ours)
+}
+
+// defended by a friendly pawn or standing next to one
+pub fn connected_pawns(ours : u64 , player : Player) -> u64 {
+    ours & (pawn_attacks(ours, player) | shift_east(ours) | shift_west(ours))
+}
+
+// not passed yet, but nothing on its file in front and at least as many helpers as sentries
+pub fn candidate_pawns(ours : u64 , theirs : u64 , player : Player) -> u64 {
+    let mut pawns = ours & !passed_pawns(ours, theirs, player);
+    let mut candidates = 0;
+
+    loop {
+        let index = get_lsb(pawns);
+        if index == 64 {
+            break;
+        }
+        let pawn = 1u64 << index;
+        let span = front_span(pawn, player);
+
+        if span & theirs == 0 {
+            let sentries = (shift_east(span) | shift_west(span)) & theirs;
+            let behind = rear_span(pawn, player) | pawn;
+            let helpers = (shift_east(behind) | shift_west(behind)) & ours;
+
+            if helpers.count_ones() >= sentries.count_ones() {
+                candidates |= pawn;
+            }
+        }
+        flip_bit(&mut pawns, index);
+    }
+
+    candidates
+}
+
+pub fn pawn_islands(ours : u64) -> u32 {
+    let files = south_fill(ours) & RANK_1;
+    (files & !(files << 1)).count_ones()
+}
+
+
+fn sum_by_rank(mut pawns : u64 , player : Player , table : &[Score ; 8]) -> Score {
+    let mut score = Score::default();
+    loop {
+        let index = get_lsb(pawns);
+        if index == 64 {
+            break;
+        }
+        score += table[relative_rank(index, player)];
+        flip_bit(&mut pawns, index);
+    }
+    score
+}
+
+fn evaluate_pawns_for(ours : u64 , theirs : u64 , player : Player , params : &EvalParams) -> Score {
+    let mut score = Score::default();
+
+    score += params.isolated_pawn * isolated_pawns(ours).count_ones() as i32;
+    score += params.doubled_pawn * doubled_pawns(ours, player).count_ones() as i32;
+    score += params.backward_pawn * backward_pawns(ours, theirs, player).count_ones() as i32;
+    score += params.pawn_island * pawn_islands(ours).saturating_sub(1) as i32;
+    score += sum_by_rank(connected_pawns(ours, player), player, &params.connected_pawn);
+    score += sum_by_rank(candidate_pawns(ours, theirs, player), player, &params.candidate_pawn);
+
+    score
+}
+
+
+// everything about the pawn structure that depends on pawns alone
+#[derive(Clone, Default)]
+pub struct PawnEntry {
+    pub key : u64,
+    pub scores : [Score ; 2],
+    pub passed : [u64 ; 2],
+}
+
+pub fn evaluate_pawn_structure(white_pawns : u64 , black_pawns : u64 , key : u64 , params : &EvalParams) -> PawnEntry {
+    PawnEntry {
+        key,
+        scores : [
+            evaluate_pawns_for(white_pawns, black_pawns, Player::White, params),
+            evaluate_pawns_for(black_pawns, white_pawns, Player::Black, params),
+        ],
+        passed : [
+            passed_pawns(white_pawns, black_pawns, Player::White),
+            passed_pawns(black_pawns, white_pawns, Player::Black),
+        ],
+    }
+}
+
+// passed pawn bonuses depend on the pieces in front of them, so they are scored outside the cache
+pub fn evaluate_passed_pawns(board : &BitBoard , pawn_entry : &PawnEntry , player : Player , params : &EvalParams) -> Score {
+    let enemy = if player == Player::White { &board.black_set } else { &board.white_set };
+    let mut passed = pawn_entry.passed[player as usize];
+    let mut score = Score::default();
+
+    loop {
+        let index = get_lsb(passed);
+        if index == 64 {
+            break;
+        }
+        let rank = relative_rank(index, player);
+        score += if push(1u64 << index, player) & enemy.occupied != 0 {
+            params.blocked_passed_pawn[rank]
+        } else {
+            params.passed_pawn[rank]
+        };
+        flip_bit(&mut passed, index);
+    }
+
+    score
+}
+
+
+pub struct PawnHashTable {
+    entries : Vec<PawnEntry>,
+    mask : usize,
+}
+
+impl PawnHashTable {
+    // `size` is rounded up to a power of two
+    pub fn new(size : usize) -> Self {
+        let size = size.max(1).next_power_of_two();
+        PawnHashTable {
+            entries : vec![PawnEntry::default() ; size],
+            mask : size - 1,
+        }
+    }
+
+    // an empty slot has key 0, which is also the key (and the correct entry) of a pawnless board;
+    // entries are computed with `params`, so the table must be cleared when they change
+    pub fn probe(&mut self, board : &BitBoard , params : &EvalParams) -> &PawnEntry {
+        let key = board.pawn_hash;
+        let slot = key as usize & self.mask;
+
+        if self.entries[slot].key != key {
+            self.entries[slot] = evaluate_pawn_structure(board.white_set.pawns, board.black_set.pawns, key, params);
+        }
+
+        &self.entries[slot]
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(PawnEntry::default());
+    }
+}
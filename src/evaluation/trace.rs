@@ -0,0 +1,93 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use super::Score;
+
+
+// one evaluation term for each side; the side's own contribution, before anything is subtracted
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize)]
+pub struct TermTrace {
+    pub white : Score,
+    pub black : Score,
+}
+
+impl TermTrace {
+    pub fn new(white : Score , black : Score) -> Self {
+        TermTrace { white, black }
+    }
+
+    pub fn total(&self) -> Score {
+        self.white - self.black
+    }
+}
+
+// every step of the handcrafted evaluation, from the individual terms to the final score
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct EvalTrace {
+    pub material : TermTrace,
+    pub psqt : TermTrace,
+    pub pawns : TermTrace,
+    pub passed_pawns : TermTrace,
+    pub king_safety : TermTrace,
+    pub mobility : TermTrace,
+    pub pieces : TermTrace,
+    pub imbalance : TermTrace,
+    pub tempo : TermTrace,
+    // sum of the terms, white minus black
+    pub total : Score,
+    // out of SCALE_NORMAL, applied to the endgame half of `total`
+    pub scale_factor : i32,
+    pub scaled_eg : i32,
+    // MAX_PHASE with every piece on the board, 0 in a pawn ending
+    pub phase : i32,
+    // tapered score from white's point of view
+    pub blended : i32,
+    // tapered score from the side to move, what `evaluate` returns
+    pub eval : i32,
+}
+
+impl EvalTrace {
+    pub fn terms(&self) -> [(&'static str, TermTrace) ; 9] {
+        [
+            ("Material", self.material),
+            ("PSQT", self.psqt),
+            ("Pawns", self.pawns),
+            ("Passed pawns", self.passed_pawns),
+            ("King safety", self.king_safety),
+            ("Mobility", self.mobility),
+            ("Pieces", self.pieces),
+            ("Imbalance", self.imbalance),
+            ("Tempo", self.tempo),
+        ]
+    }
+}
+
+fn write_score(f : &mut fmt::Formatter , score : Score) -> fmt::Result {
+    write!(f, " {:>6} {:>6} |", score.mg, score.eg)
+}
+
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "         Term |     White     |     Black     |     Total     |")?;
+        writeln!(f, "              |     MG     EG |     MG     EG |     MG     EG |")?;
+        writeln!(f, " -------------+---------------+---------------+---------------+")?;
+        for (name, term) in self.terms() {
+            write!(f, " {:>12} |", name)?;
+            write_score(f, term.white)?;
+            write_score(f, term.black)?;
+            write_score(f, term.total())?;
+            writeln!(f)?;
+        }
+        writeln!(f, " -------------+---------------+---------------+---------------+")?;
+        write!(f, " {:>12} |               |               |", "Total")?;
+        write_score(f, self.total)?;
+        writeln!(f)?;
+
+        writeln!(f)?;
+        writeln!(f, " Scale factor  {} (endgame {} -> {})", self.scale_factor, self.total.eg, self.scaled_eg)?;
+        writeln!(f, " Phase         {}", self.phase)?;
+        writeln!(f, " Blended       {} (white side)", self.blended)?;
+        write!(f, " Evaluation    {} (side to move)", self.eval)
+    }
+}
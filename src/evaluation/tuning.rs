@@ -0,0 +1,184 @@
+use std::thread;
+
+use crate::{bit_board::BitBoard, player::Player};
+
+use super::dataset::{load_lines, parse_result};
+use super::evaluate_with_params;
+use super::params::EvalParams;
+
+
+const ADAM_BETA1 : f64 = 0.9;
+const ADAM_BETA2 : f64 = 0.999;
+const ADAM_EPSILON : f64 = 1e-8;
+
+
+pub struct TuningPosition {
+    pub board : BitBoard,
+    // 1 / 0.5 / 0 for a white win / draw / loss
+    pub result : f64,
+}
+
+impl TuningPosition {
+    // an EPD record or a full FEN, followed by the result in any of the usual notations
+    pub fn parse(line : &str) -> Result<Self, String> {
+        let fields : Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 5 {
+            return Err(format!("Expected a position and a result, found \"{}\"", line));
+        }
+
+        // EPD records stop after the en passant square, FENs carry the two move counters
+        let has_counters = fields.len() >= 7 && fields[4].parse::<u32>().is_ok() && fields[5].parse::<u32>().is_ok();
+        let (fen, rest) = if has_counters {
+            (fields[..6].join(" "), fields[6..].join(" "))
+        } else {
+            (format!("{} 0 1", fields[..4].join(" ")), fields[4..].join(" "))
+        };
+
+        let result = parse_result(&rest).ok_or_else(|| format!("Missing result in \"{}\"", line))?;
+        Ok(TuningPosition { board : BitBoard::fen_to_valid_bitboard(&fen)?, result })
+    }
+}
+
+// skips blank lines and lines starting with '#'
+pub fn load_positions(path : &str) -> Result<Vec<TuningPosition>, String> {
+    load_lines(path, TuningPosition::parse)
+}
+
+
+// expected score for white given an evaluation in centipawns from white's side
+pub fn win_probability(eval : f64 , k : f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval / 400.0))
+}
+
+fn white_eval(board : &BitBoard , params : &EvalParams) -> f64 {
+    let eval = evaluate_with_params(board, params) as f64;
+    if board.player == Player::White { eval } else { -eval }
+}
+
+// white-side evaluations of every position, split across threads
+pub fn evaluate_all(positions : &[TuningPosition] , params : &EvalParams , threads : usize) -> Vec<f64> {
+    if positions.is_empty() {
+        return Vec::new();
+    }
+    let chunk_size = positions.len().div_ceil(threads.max(1));
+
+    thread::scope(|scope| {
+        let workers : Vec<_> = positions.chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(|position| white_eval(&position.board, params)).collect::<Vec<_>>()))
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    })
+}
+
+fn error_of(positions : &[TuningPosition] , evals : &[f64] , k : f64) -> f64 {
+    let total : f64 = positions.iter().zip(evals).map(|(position, eval)| {
+        let difference = position.result - win_probability(*eval, k);
+        difference * difference
+    }).sum();
+    total / positions.len().max(1) as f64
+}
+
+// mean squared difference between the results and the predicted win probabilities
+pub fn mean_error(positions : &[TuningPosition] , params : &EvalParams , k : f64 , threads : usize) -> f64 {
+    error_of(positions, &evaluate_all(positions, params, threads), k)
+}
+
+// the K that best maps the current evaluation onto the results, refined one decimal at a time
+pub fn fit_k(positions : &[TuningPosition] , params : &EvalParams , threads : usize) -> f64 {
+    let evals = evaluate_all(positions, params, threads);
+    let mut best = 1.0;
+    let mut step = 0.5;
+
+    for _ in 0..6 {
+        let candidates = (-10..=10).map(|offset| best + step * offset as f64 / 5.0).filter(|k| *k > 0.0);
+        best = candidates.fold((best, error_of(positions, &evals, best)), |(best, best_error), k| {
+            let error = error_of(positions, &evals, k);
+            if error < best_error { (k, error) } else { (best, best_error) }
+        }).0;
+        step /= 5.0;
+    }
+
+    best
+}
+
+
+#[derive(Clone, Copy, Debug)]
+pub struct TunerConfig {
+    pub k : f64,
+    pub iterations : usize,
+    // step size of gradient descent, in parameter units
+    pub learning_rate : f64,
+    pub threads : usize,
+}
+
+// Texel's local search: nudge every weight by one in either direction and keep what helps,
+// until a full pass changes nothing; `report` gets the pass number and the error after it
+pub fn local_search(positions : &[TuningPosition] , start : &EvalParams , config : TunerConfig , report : &mut dyn FnMut(usize, f64)) -> EvalParams {
+    let mut values = start.to_vector();
+    let mut params = start.clone();
+    let mut best_error = mean_error(positions, &params, config.k, config.threads);
+
+    for iteration in 1..=config.iterations {
+        let mut improved = false;
+
+        for index in 0..values.len() {
+            for delta in [1, -1] {
+                values[index] += delta;
+                params.set_from_vector(&values);
+                let error = mean_error(positions, &params, config.k, config.threads);
+                if error < best_error {
+                    best_error = error;
+                    improved = true;
+                    break;
+                }
+                values[index] -= delta;
+            }
+        }
+
+        params.set_from_vector(&values);
+        report(iteration, best_error);
+        if !improved {
+            break;
+        }
+    }
+
+    params
+}
+
+// Adam on central finite differences; weights are kept as floats and rounded for every evaluation
+pub fn gradient_descent(positions : &[TuningPosition] , start : &EvalParams , config : TunerConfig , report : &mut dyn FnMut(usize, f64)) -> EvalParams {
+    let mut weights : Vec<f64> = start.to_vector().iter().map(|value| *value as f64).collect();
+    let mut first_moments = vec![0.0 ; weights.len()];
+    let mut second_moments = vec![0.0 ; weights.len()];
+    let round = |weights : &[f64]| -> Vec<i32> { weights.iter().map(|weight| weight.round() as i32).collect() };
+
+    let mut params = start.clone();
+    for iteration in 1..=config.iterations {
+        let mut values = round(&weights);
+        let mut gradient = vec![0.0 ; weights.len()];
+
+        for index in 0..values.len() {
+            values[index] += 1;
+            params.set_from_vector(&values);
+            let above = mean_error(positions, &params, config.k, config.threads);
+            values[index] -= 2;
+            params.set_from_vector(&values);
+            let below = mean_error(positions, &params, config.k, config.threads);
+            values[index] += 1;
+            gradient[index] = (above - below) / 2.0;
+        }
+
+        let step_size = config.learning_rate * (1.0 - ADAM_BETA2.powi(iteration as i32)).sqrt()
+            / (1.0 - ADAM_BETA1.powi(iteration as i32));
+        for (((weight, gradient), m), v) in weights.iter_mut().zip(&gradient).zip(&mut first_moments).zip(&mut second_moments) {
+            *m = ADAM_BETA1 * *m + (1.0 - ADAM_BETA1) * gradient;
+            *v = ADAM_BETA2 * *v + (1.0 - ADAM_BETA2) * gradient * gradient;
+            *weight -= step_size * *m / (v.sqrt() + ADAM_EPSILON);
+        }
+
+        params.set_from_vector(&round(&weights));
+        report(iteration, mean_error(positions, &params, config.k, config.threads));
+    }
+
+    params
+}
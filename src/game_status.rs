@@ -0,0 +1,96 @@
+use std::fmt;
+
+use crate::{bit_board::BitBoard, player::Player, utils::{DARK_SQUARES, LIGHT_SQUARES}};
+
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GameStatus {
+    Ongoing,
+    // the player who delivered mate
+    Checkmate(Player),
+    Stalemate,
+    // the draws a player may claim
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    // the draws that end the game on their own
+    FivefoldRepetition,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+}
+
+impl fmt::Display for GameStatus {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameStatus::Ongoing => write!(f, "The game is still going"),
+            GameStatus::Checkmate(winner) => write!(f, "Checkmate, {:?} wins", winner),
+            GameStatus::Stalemate => write!(f, "Draw by stalemate"),
+            GameStatus::FiftyMoveRule => write!(f, "Draw by the fifty move rule"),
+            GameStatus::ThreefoldRepetition => write!(f, "Draw by threefold repetition"),
+            GameStatus::FivefoldRepetition => write!(f, "Draw by fivefold repetition"),
+            GameStatus::SeventyFiveMoveRule => write!(f, "Draw by the seventy-five move rule"),
+            GameStatus::InsufficientMaterial => write!(f, "Draw by insufficient material"),
+        }
+    }
+}
+
+
+impl BitBoard {
+    // mate and stalemate come first, a move that mates on the hundredth ply still wins
+    pub fn game_status(&mut self) -> GameStatus {
+        if !self.has_legal_move() {
+            return if self.king_in_check(self.player) {
+                GameStatus::Checkmate(!self.player)
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+
+        let repetitions = self.repetitions();
+        if self.insufficient_material() {
+            GameStatus::InsufficientMaterial
+        } else if repetitions >= 5 {
+            GameStatus::FivefoldRepetition
+        } else if self.halfmove_clock >= 150 {
+            GameStatus::SeventyFiveMoveRule
+        } else if repetitions >= 3 {
+            GameStatus::ThreefoldRepetition
+        } else if self.halfmove_clock >= 100 {
+            GameStatus::FiftyMoveRule
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
+    pub fn has_legal_move(&mut self) -> bool {
+        !self.legal_moves().is_empty()
+    }
+
+    // how many times the current position has occurred, counting only positions reached through
+    // `make_move` since the last capture or pawn move, with the same side to move
+    pub fn repetitions(&self) -> usize {
+        let key = self.key();
+        let reversible = (self.halfmove_clock as usize).min(self.history.len());
+
+        1 + self.history[self.history.len() - reversible..].iter().rev()
+            .skip(1)
+            .step_by(2)
+            .filter(|state| state.key == key)
+            .count()
+    }
+
+    // neither side can mate: bare kings, a single minor piece, or only bishops all on one colour
+    pub fn insufficient_material(&self) -> bool {
+        let (white, black) = (&self.white_set, &self.black_set);
+        if white.pawns | black.pawns | white.rooks | black.rooks | white.queens | black.queens != 0 {
+            return false;
+        }
+
+        let knights = white.knights | black.knights;
+        let bishops = white.bishops | black.bishops;
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+
+        knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & DARK_SQUARES == 0)
+    }
+}
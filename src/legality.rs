@@ -0,0 +1,120 @@
+use crate::{attack_maps::{BETWEEN, KING_ATTACKS, KNIGHT_JUMPS, PAWN_CAPTURES},
+bit_board::{BitBoard, PieceType}, engine::castle_destinations,
+move_generator::{generate_diagonal_moves, generate_straight_moves}, player::Player, utils::{flip_bit, get_lsb, move_to_uci, RANK_1, RANK_8}};
+
+
+const EN_PESSANT : u16 = 5;
+
+const CASTLE_KING : u16 = 6;
+const CASTLE_QUEEN : u16 = 7;
+
+const DOUBLE_PAWN_PUSH : u16 = 8;
+
+
+impl BitBoard {
+    // whether `mov` is one `generate_moves` would produce for the side to move; any u16 is accepted
+    pub fn is_pseudo_legal(&self , mov : u16) -> bool {
+        let turn = self.player;
+        let (ally, enemy) = if turn == Player::White { (&self.white_set, &self.black_set) } else { (&self.black_set, &self.white_set) };
+
+        let src = mov as usize & 0x3F;
+        let dest = (mov as usize >> 6) & 0x3F;
+        let special = mov >> 12;
+        let occupied = ally.occupied | enemy.occupied;
+
+        let piece_type = match self.mailbox[src] {
+            Some((player, piece_type)) if player == turn => piece_type,
+            _ => return false,
+        };
+
+        if special == CASTLE_KING || special == CASTLE_QUEEN {
+            let kingside = special == CASTLE_KING;
+            if piece_type != PieceType::King || ally.castle_rooks & (1u64 << dest) == 0
+                || dest & 56 != src & 56 || (dest > src) != kingside {
+                return false;
+            }
+
+            let (king_dest, rook_dest) = castle_destinations(src, kingside);
+            let king_path = BETWEEN[src][king_dest] | 1u64 << king_dest;
+            let rook_path = BETWEEN[dest][rook_dest] | 1u64 << rook_dest;
+            let must_be_empty = (king_path | rook_path) & !(1u64 << src | 1u64 << dest);
+            if occupied & must_be_empty != 0 {
+                return false;
+            }
+
+            let mut must_be_safe = king_path | 1u64 << src;
+            loop {
+                let index = get_lsb(must_be_safe);
+                if index == 64 {
+                    return true;
+                }
+                if self.attackers_to(index, occupied) & enemy.occupied != 0 {
+                    return false;
+                }
+                flip_bit(&mut must_be_safe, index);
+            }
+        }
+
+        if ally.occupied & (1u64 << dest) != 0 {
+            return false;
+        }
+
+        if piece_type != PieceType::Pawn {
+            let attacks = match piece_type {
+                PieceType::Knight => KNIGHT_JUMPS[src],
+                PieceType::Bishop => generate_diagonal_moves(src, occupied),
+                PieceType::Rook => generate_straight_moves(src, occupied),
+                PieceType::Queen => generate_diagonal_moves(src, occupied) | generate_straight_moves(src, occupied),
+                _ => KING_ATTACKS[src],
+            };
+            return special == 0 && attacks & (1u64 << dest) != 0;
+        }
+
+        let (forward, start_rank, last_rank) = if turn == Player::White { (8i32, 1, RANK_8) } else { (-8i32, 6, RANK_1) };
+        let single = src as i32 + forward;
+        let promotes = last_rank & (1u64 << dest) != 0;
+        let capture = PAWN_CAPTURES[turn as usize][src] & (1u64 << dest) != 0;
+
+        match special {
+            EN_PESSANT => capture && enemy.double_push_pawns & (1u64 << dest) != 0 && occupied & (1u64 << dest) == 0,
+            DOUBLE_PAWN_PUSH => {
+                src / 8 == start_rank && dest as i32 == single + forward
+                    && occupied & (1u64 << single | 1u64 << dest) == 0
+            }
+            0..=4 => {
+                // promotions are the only way onto the last rank
+                if promotes != (special != 0) {
+                    return false;
+                }
+                if capture {
+                    enemy.occupied & (1u64 << dest) != 0
+                } else {
+                    dest as i32 == single && occupied & (1u64 << dest) == 0
+                }
+            }
+            _ => false,
+        }
+    }
+
+    // a pseudo-legal move that does not leave the mover's king in check
+    pub fn is_legal(&mut self , mov : u16) -> bool {
+        if !self.is_pseudo_legal(mov) {
+            return false;
+        }
+
+        let turn = self.player;
+        self.make_move(mov);
+        let legal = !self.king_in_check(turn);
+        self.unmake_move();
+        legal
+    }
+
+    // `make_move` for moves that come from outside the move generator: hash and killer moves, books, the network
+    pub fn try_apply_move(&mut self , mov : u16) -> Result<(), String> {
+        if !self.is_legal(mov) {
+            return Err(format!("Illegal move {} ({:#06x})", move_to_uci(mov, false), mov));
+        }
+        self.make_move(mov);
+        Ok(())
+    }
+}
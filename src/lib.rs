@@ -0,0 +1,19 @@
+pub mod piece_set;
+pub mod bit_board;
+pub mod attack_maps;
+pub mod magic;
+pub mod utils;
+pub mod move_generator;
+pub mod move_list;
+pub mod engine;
+pub mod player;
+pub mod zobrist;
+pub mod validation;
+pub mod game_status;
+pub mod legality;
+pub mod perft;
+pub mod pleco_check;
+pub mod polyglot;
+pub mod evaluation;
+pub mod server;
+mod tests;
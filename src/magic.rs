@@ -0,0 +1,202 @@
+use lazy_static::lazy_static;
+use rand::{rngs::StdRng, Rng};
+
+use crate::attack_maps::{DIAGONAL_RAYS, STRAIGHT_RAYS};
+use crate::move_generator::{diagonal_ray_attacks, straight_ray_attacks};
+use crate::utils::{FILE_A, FILE_H, RANK_1, RANK_8};
+
+lazy_static! {
+    pub static ref ROOK_MAGICS: SliderTable = SliderTable::new(rook_mask, straight_ray_attacks, &ROOK_MAGIC_NUMBERS);
+    pub static ref BISHOP_MAGICS: SliderTable = SliderTable::new(bishop_mask, diagonal_ray_attacks, &BISHOP_MAGIC_NUMBERS);
+}
+
+// found by `find_magic` with `StdRng::seed_from_u64(0x2545_F491_4F6C_DD1D)`, one square after another
+pub const ROOK_MAGIC_NUMBERS : [u64 ; 64] = [
+    0x8080002010804002, 0x4840400020001000, 0x0200200A00801040, 0x0100100008050020,
+    0x0100020801000410, 0x0600104802000104, 0x0080010000800200, 0x0300019042210002,
+    0x0040800080400020, 0x3201402000401000, 0x1011001100442004, 0x0321002010000902,
+    0x0800800800040081, 0x2020800400800200, 0x1684000102080410, 0x0011001463810002,
+    0xB400908000204004, 0x6010044005200040, 0x506B010020001042, 0x4800808008001000,
+    0x0001010008001004, 0x0800808002000401, 0x8304040008100201, 0x0120020000840061,
+    0x9081748480024001, 0x61004000C0201000, 0x0060080040100044, 0x021000210011000B,
+    0x00C0080280040080, 0x0202000200051008, 0x0A8428040001C210, 0x8403210200208044,
+    0x2680002000C0005C, 0x0000400084802001, 0x0010002001010040, 0x5011001001000822,
+    0x2100800800800400, 0x0008040080800200, 0x0002411004004802, 0x0110010042000084,
+    0x0142248240088000, 0x0C00500020004000, 0x0060200010008080, 0x0011001000210008,
+    0x0400040008008080, 0x8002000204008080, 0x00008801022400D0, 0x0200084100860004,
+    0x0210264008800180, 0x0400400101259700, 0x0400102000410100, 0x0400821000080280,
+    0x0080080080440280, 0x0400201040040801, 0x6040480201100400, 0x020C04A849040200,
+    0x0100420080102102, 0x2101220240108502, 0x8080081500402001, 0x0042004008041022,
+    0x4042000920100402, 0x80010002080C0069, 0x2040011200C81004, 0x0002010020408402,
+];
+
+pub const BISHOP_MAGIC_NUMBERS : [u64 ; 64] = [
+    0x0002208A14004080, 0x0004100202022C40, 0x2008808906021240, 0x0024404080060080,
+    0x0404042000010810, 0x088104A004004140, 0x0204008248200000, 0x0200108084104040,
+    0x000440080800C080, 0x0888203441120222, 0x0008080849002010, 0x0800040410810000,
+    0x0001040420140100, 0x5008342220900010, 0x0402020084644084, 0xA04002808088A020,
+    0x000A004008280084, 0x01020020080A6080, 0x6008004042002020, 0x0B64000240148022,
+    0x0084008210140100, 0x4251800808040A40, 0x004D0000842120A2, 0x0061000602460240,
+    0x4420045111346832, 0x0C41088004502408, 0x4302010608080020, 0x0002080264010420,
+    0x0002040002010840, 0xE208002001100805, 0x9088162000410404, 0x0220410000908804,
+    0x381010440C106410, 0x0014504200480200, 0x1480404800104809, 0x0020020080080080,
+    0x00200B0240240140, 0x4600E08100020100, 0x4515031201640220, 0x2824404080160080,
+    0x0881100220041080, 0x00D0841002A00840, 0x0102002201000828, 0x0041022124009808,
+    0x0010102012000040, 0x0848100082000024, 0xA110120204040360, 0x010802006A088041,
+    0x2014240104100000, 0x0186820110220010, 0x4000304C04040600, 0x8641000020880041,
+    0x4004202803040000, 0x8002506008812002, 0x014008420C086030, 0x8008210820810830,
+    0x0100840410844412, 0x6010320200C42400, 0x0040012021080888, 0x000028841020A810,
+    0xC0A0002010021202, 0x000A000890100080, 0x0800200510208100, 0x1504200400588900,
+];
+
+
+#[derive(Clone, Copy, Default)]
+pub struct Magic {
+    // relevant blockers, the edge squares never change the attacks
+    pub mask : u64,
+    pub magic : u64,
+    pub shift : u32,
+    // start of this square's slice of the shared attack table
+    pub offset : usize,
+}
+
+pub struct SliderTable {
+    pub magics : [Magic ; 64],
+    pub attacks : Vec<u64>,
+    // index with BMI2 PEXT instead of the magic multiply
+    pub pext : bool,
+}
+
+
+pub fn rook_mask(index : usize) -> u64 {
+    (STRAIGHT_RAYS[0][index] & !RANK_8)
+        | (STRAIGHT_RAYS[1][index] & !FILE_H)
+        | (STRAIGHT_RAYS[2][index] & !RANK_1)
+        | (STRAIGHT_RAYS[3][index] & !FILE_A)
+}
+
+pub fn bishop_mask(index : usize) -> u64 {
+    (DIAGONAL_RAYS[0][index] | DIAGONAL_RAYS[1][index] | DIAGONAL_RAYS[2][index] | DIAGONAL_RAYS[3][index])
+        & !(FILE_A | FILE_H | RANK_1 | RANK_8)
+}
+
+// every subset of `mask`, in the order PEXT numbers them
+pub fn subsets(mask : u64) -> Vec<u64> {
+    let mut subsets = Vec::with_capacity(1 << mask.count_ones());
+    let mut subset = 0u64;
+    loop {
+        subsets.push(subset);
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+    subsets
+}
+
+fn pext_available() -> bool {
+    #[cfg(all(feature = "pext", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("bmi2") {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(all(feature = "pext", target_arch = "x86_64"))]
+#[target_feature(enable = "bmi2")]
+unsafe fn pext(occupied : u64 , mask : u64) -> u64 {
+    std::arch::x86_64::_pext_u64(occupied, mask)
+}
+
+// a sparse random number that maps every blocker subset to a slot without a harmful collision,
+// `attacks` holding the attacks for each of `occupancies`
+pub fn find_magic(mask : u64 , occupancies : &[u64] , attacks : &[u64] , rng : &mut StdRng) -> u64 {
+    let bits = mask.count_ones();
+    let mut used = vec![0u64 ; occupancies.len()];
+    let mut epoch = vec![0u32 ; occupancies.len()];
+
+    for attempt in 1u32.. {
+        let magic = rng.random::<u64>() & rng.random::<u64>() & rng.random::<u64>();
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+
+        let fits = occupancies.iter().zip(attacks).all(|(occupied, attack)| {
+            let slot = (occupied.wrapping_mul(magic) >> (64 - bits)) as usize;
+            if epoch[slot] != attempt {
+                epoch[slot] = attempt;
+                used[slot] = *attack;
+                true
+            } else {
+                used[slot] == *attack
+            }
+        });
+
+        if fits {
+            return magic;
+        }
+    }
+    unreachable!()
+}
+
+impl SliderTable {
+    fn new(mask_of : fn(usize) -> u64 , attacks_of : fn(usize, u64) -> u64 , magic_numbers : &[u64 ; 64]) -> SliderTable {
+        let pext = pext_available();
+        let mut magics = [Magic::default() ; 64];
+        let mut table = Vec::new();
+
+        for (index, entry) in magics.iter_mut().enumerate() {
+            let mask = mask_of(index);
+            let bits = mask.count_ones();
+            let occupancies = subsets(mask);
+            let attacks : Vec<u64> = occupancies.iter().map(|occupied| attacks_of(index, *occupied)).collect();
+
+            *entry = Magic {
+                mask,
+                magic : magic_numbers[index],
+                shift : 64 - bits,
+                offset : table.len(),
+            };
+
+            table.resize(table.len() + (1 << bits), 0);
+            for (occupied, attack) in occupancies.iter().zip(&attacks) {
+                let slot = entry.offset + SliderTable::slot(entry, pext, *occupied);
+                // sliders always attack something, so an empty slot is zero
+                debug_assert!(table[slot] == 0 || table[slot] == *attack, "bad magic for square {}", index);
+                table[slot] = *attack;
+            }
+        }
+
+        SliderTable { magics, attacks : table, pext }
+    }
+
+    #[inline(always)]
+    fn slot(entry : &Magic , pext_enabled : bool , occupied : u64) -> usize {
+        #[cfg(all(feature = "pext", target_arch = "x86_64"))]
+        {
+            if pext_enabled {
+                // only set when the CPU reported BMI2
+                return unsafe { pext(occupied, entry.mask) } as usize;
+            }
+        }
+        let _ = pext_enabled;
+        ((occupied & entry.mask).wrapping_mul(entry.magic) >> entry.shift) as usize
+    }
+
+    #[inline(always)]
+    pub fn attacks(&self , index : usize , occupied : u64) -> u64 {
+        let entry = &self.magics[index];
+        self.attacks[entry.offset + SliderTable::slot(entry, self.pext, occupied)]
+    }
+}
+
+
+pub fn rook_attacks(index : usize , occupied : u64) -> u64 {
+    ROOK_MAGICS.attacks(index, occupied)
+}
+
+pub fn bishop_attacks(index : usize , occupied : u64) -> u64 {
+    BISHOP_MAGICS.attacks(index, occupied)
+}
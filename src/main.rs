@@ -1,24 +1,115 @@
 
 
-use bit_board::BitBoard;
+use std::{env, process};
 
-pub mod piece_set;
-pub mod bit_board;
-pub mod attack_maps;
-pub mod utils;
-pub mod move_generator;
-pub mod engine;
-pub mod player;
-mod tests;
+use rust_chess_engine::bit_board::BitBoard;
+use rust_chess_engine::evaluation::Evaluator;
+use rust_chess_engine::server;
+use rust_chess_engine::polyglot::PolyglotBook;
+use rust_chess_engine::utils::move_to_uci;
 
 
 
 // extern crate pleco;
 
 
-fn main() {
-    let board = BitBoard::get_starting_board();
+#[derive(Default)]
+struct Options {
+    fen : Option<String>,
+    // (engine option, value) pairs, applied in order once the board is set up
+    engine_options : Vec<(&'static str, String)>,
+    trace : bool,
+    trace_json : bool,
+    serve : Option<String>,
+    book : Option<String>,
+    book_depth : Option<u32>,
+    book_random : bool,
+    play : bool,
+}
+
+// startup flags map onto the engine options of the same meaning
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--fen" => options.fen = Some(value()?),
+            "--eval-params" => options.engine_options.push(("EvalParams", value()?)),
+            "--nnue" => options.engine_options.push(("EvalFile", value()?)),
+            "--trace" => options.trace = true,
+            "--trace-json" => options.trace_json = true,
+            "--serve" => options.serve = Some(value()?),
+            "--book" => options.book = Some(value()?),
+            "--book-depth" => {
+                let depth = value()?;
+                options.book_depth = Some(depth.parse().map_err(|_| format!("Invalid value for --book-depth: {}", depth))?);
+            }
+            "--book-random" => options.book_random = true,
+            "--play" => options.play = true,
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+fn run(options : Options) -> Result<(), String> {
+    let mut board = match &options.fen {
+        Some(fen) => BitBoard::fen_to_valid_bitboard(fen)?,
+        None => BitBoard::get_starting_board(),
+    };
+
+    let mut evaluator = Evaluator::new();
+    for (name, value) in &options.engine_options {
+        evaluator.set_option(name, value, &board)?;
+    }
+
     board.print_board();
-    
+    println!("eval: {}", evaluator.evaluate(&board));
+
+    if options.trace {
+        println!("{}", evaluator.trace(&board));
+    }
+    if options.trace_json {
+        let json = serde_json::to_string_pretty(&evaluator.trace(&board))
+            .map_err(|e| format!("Failed to serialize trace: {}", e))?;
+        println!("{}", json);
+    }
+
+    let mut book = match &options.book {
+        Some(path) => {
+            let mut book = PolyglotBook::open(path)?;
+            book.random = options.book_random;
+            if let Some(depth) = options.book_depth {
+                book.max_depth = depth;
+            }
+            Some(book)
+        }
+        None => None,
+    };
+
+    if options.play {
+        // book moves are played until the book runs out, the rest is read from the input
+        board.run_engine(board.player, book.as_mut());
+    } else if let Some(book) = &mut book {
+        match book.pick(&mut board)? {
+            Some(mov) => println!("book move: {}", move_to_uci(mov, false)),
+            None => println!("book move: none"),
+        }
+    }
+
+    if let Some(address) = &options.serve {
+        println!("serving evaluations on {}", address);
+        server::serve(address, evaluator)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let result = parse_options().and_then(run);
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 
 } 
@@ -1,6 +1,7 @@
 
-use crate::{attack_maps::{DIAGONAL_RAYS, KING_ATTACKS, KNIGHT_JUMPS, PAWN_CAPTURES, PAWN_PUSH, STRAIGHT_RAYS}, 
-piece_set::PieceSet, player::Player, utils::{flip_bit, get_lsb, test_bit}};
+use crate::{attack_maps::{BETWEEN, DIAGONAL_RAYS, KING_ATTACKS, KNIGHT_JUMPS, PAWN_CAPTURES, PAWN_PUSH, STRAIGHT_RAYS}, 
+engine::castle_destinations, magic::{bishop_attacks, rook_attacks}, move_list::MoveList, piece_set::PieceSet, player::Player,
+utils::{flip_bit, get_lsb, test_bit, RANK_1}};
 
 
 
@@ -16,29 +17,21 @@ const CASTLE_QUEEN : u16 = 7;
 
 const DOUBLE_PAWN_PUSH : u16= 8;
 
-const WHITE_KING_START: usize = 4;
-const BLACK_KING_START: usize = 60;
-
-const WHITE_KINGSIDE_MASK: u64 = 1u64 << 5 | 1u64 << 6; 
-const WHITE_KINGSIDE_MASK_WITH_KING : u64 = WHITE_KINGSIDE_MASK | 1 << 4;
-
-const WHITE_QUEENSIDE_MASK: u64 = 1 << 1 | 1 << 2 | 1 << 3;
-const WHITE_SHORT_QUEENSIDE_MASK: u64 = 1 << 2 | 1 << 3;
-const WHITE_QUEENSIDE_MASK_WITH_KING : u64 = WHITE_SHORT_QUEENSIDE_MASK | 1 << 4;
-
-const BLACK_KINGSIDE_MASK: u64 = 1u64 << 61 | 1u64 << 62;
-const BLACK_KINGSIDE_MASK_WITH_KING : u64 = BLACK_KINGSIDE_MASK | 1u64 << 60;
+pub fn generate_diagonal_moves(index : usize , occupied : u64) -> u64 {
+    bishop_attacks(index, occupied)
+}
 
-const BLACK_QUEENSIDE_MASK: u64 = 1u64 << 57 | 1u64 << 59 | 1u64 << 58 ;
-const BLACK_SHORT_QUEENSIDE_MASK: u64 = 1u64 << 58 | 1u64 << 59;
-const BLACK_QUEENSIDE_MASK_WITH_KING : u64= BLACK_SHORT_QUEENSIDE_MASK | 1u64 << 60;
+pub fn generate_straight_moves(index : usize , occupied : u64) -> u64 {
+    rook_attacks(index, occupied)
+}
 
-pub fn generate_diagonal_moves(index : usize , occupied : u64) -> u64{
+// ray walks the magic tables are built from
+pub fn diagonal_ray_attacks(index : usize , occupied : u64) -> u64{
     
     let mut moves : u64 = 0;
 
-    for direction in 0..4 {
-        let ray = DIAGONAL_RAYS[direction][index];
+    for (direction, rays) in DIAGONAL_RAYS.iter().enumerate() {
+        let ray = rays[index];
         let blockers = ray & occupied;
         
         let blocker_pos = if blockers == 0{
@@ -50,17 +43,17 @@ pub fn generate_diagonal_moves(index : usize , occupied : u64) -> u64{
             }
         };
 
-        moves |= ray ^ DIAGONAL_RAYS[direction][blocker_pos];
+        moves |= ray ^ rays[blocker_pos];
     }
 
     moves
 }
 
-pub fn generate_straight_moves(index : usize , occupied : u64) -> u64 {
+pub fn straight_ray_attacks(index : usize , occupied : u64) -> u64 {
     let mut moves : u64 = 0;
 
-    for direction in 0..4 {
-        let ray = STRAIGHT_RAYS[direction][index];
+    for (direction, rays) in STRAIGHT_RAYS.iter().enumerate() {
+        let ray = rays[index];
         let blockers = ray & occupied;
 
         let blocker_pos : usize = if blockers == 0 {
@@ -72,7 +65,7 @@ pub fn generate_straight_moves(index : usize , occupied : u64) -> u64 {
             }
         };
 
-        moves |= ray ^ STRAIGHT_RAYS[direction][blocker_pos];
+        moves |= ray ^ rays[blocker_pos];
     }
 
     moves
@@ -104,36 +97,35 @@ pub fn generate_pawn_moves(index : usize , occupied : u64 , turn : usize , enemy
 }
 
 
-pub fn generate_king_moves(index : usize , occupied : u64 , castle_rooks : u64 , turn : Player , enemey_attack_map : u64) -> u64{
-    let mut moves = KING_ATTACKS[index];
+// castling with every rook that still has the right, encoded as the king taking its own rook;
+// the king always ends on the c- or g-file with the rook beside it, so this covers Chess960 too
+pub fn generate_castle_moves(king_index : usize , occupied : u64 , castle_rooks : u64 , enemy_attack_map : u64 , moves : &mut MoveList) {
+    let mut rooks = castle_rooks & (RANK_1 << (king_index & 56));
 
-    if turn == Player::White && index == WHITE_KING_START {
-        if test_bit(castle_rooks, 7) && (occupied & WHITE_KINGSIDE_MASK == 0) && (enemey_attack_map & WHITE_KINGSIDE_MASK_WITH_KING) == 0 {
-            moves |= 1 << 6;
-        }
-        if test_bit(castle_rooks, 0) && (occupied & WHITE_QUEENSIDE_MASK == 0) && (enemey_attack_map & WHITE_QUEENSIDE_MASK_WITH_KING) == 0 {
-            moves |= 1 << 2;
+    loop {
+        let rook_index = get_lsb(rooks);
+        if rook_index == 64 {
+            break;
         }
-    } 
 
-    if turn == Player::Black && index == BLACK_KING_START {
-        if test_bit(castle_rooks, 63) && (occupied & BLACK_KINGSIDE_MASK == 0) && (enemey_attack_map & BLACK_KINGSIDE_MASK_WITH_KING) == 0 {
-            moves |= 1u64 << 62;
-        }
-        
-        if test_bit(castle_rooks, 56) && (occupied & BLACK_QUEENSIDE_MASK == 0) && (enemey_attack_map & BLACK_QUEENSIDE_MASK_WITH_KING) == 0 {
-            moves |= 1u64 << 58;
-        }
-        
-    }
+        let kingside = rook_index > king_index;
+        let (king_dest, rook_dest) = castle_destinations(king_index, kingside);
+        let king_path = BETWEEN[king_index][king_dest] | 1u64 << king_dest;
+        let rook_path = BETWEEN[rook_index][rook_dest] | 1u64 << rook_dest;
+        let must_be_empty = (king_path | rook_path) & !(1u64 << king_index | 1u64 << rook_index);
 
+        if occupied & must_be_empty == 0 && enemy_attack_map & (king_path | 1u64 << king_index) == 0 {
+            let special = if kingside { CASTLE_KING } else { CASTLE_QUEEN };
+            moves.push(king_index as u16 | (rook_index << 6) as u16 | special << 12);
+        }
 
-    moves
+        flip_bit(&mut rooks, rook_index);
+    }
 }
 
 
 pub fn iterate_move_map( piece_set : &PieceSet , src_index : usize,
-    moves_for_piece : &mut u64  , mode : u8 , moves : &mut  Vec<u16> ){
+    moves_for_piece : &mut u64  , mode : u8 , moves : &mut MoveList ){
     
     loop {
         let dis_index = get_lsb(*moves_for_piece) ;
@@ -151,7 +143,7 @@ pub fn iterate_move_map( piece_set : &PieceSet , src_index : usize,
                 moves.push(mov | (BISHOP_PROMOTED << 12 ));
                 moves.push(mov | (ROOK_PROMOTED << 12 ));
             }
-            else if (distance == 7 || distance == 9) && !test_bit(piece_set.occupied, dis_index as usize) {
+            else if (distance == 7 || distance == 9) && !test_bit(piece_set.occupied, dis_index) {
                 moves.push(mov | (EN_PESSANT << 12));
             }
             else if distance == 16{
@@ -162,33 +154,25 @@ pub fn iterate_move_map( piece_set : &PieceSet , src_index : usize,
                 moves.push(mov);
             }
         } 
-        else if mode == 2 && distance >= 2 && dis_index >> 3 == src_index >> 3 {
-            if dis_index == 6 || dis_index == 62 {
-                moves.push(mov | (CASTLE_KING << 12));
-            }
-            else 
-            {
-                moves.push(mov | (CASTLE_QUEEN  << 12) );
-            }
-        }
         else
         {
             moves.push(mov);
         }
 
-        flip_bit(moves_for_piece , dis_index as usize);
+        flip_bit(moves_for_piece , dis_index);
     }
 }
 
 
 pub fn iterate_possible_move< F , T>(
     mut piece_positions : u64,
-    ally : &PieceSet,
-    enemy : &PieceSet,    
+    enemy : &PieceSet,
+    // only moves landing here are kept, it must leave out the mover's own pieces; this is how the generation stages differ
+    targets : u64,
     mode : u8 ,
     generation_function : F , 
     args : T ,
-    moves : &mut  Vec<u16>,
+    moves : &mut MoveList,
 ) 
 where 
     F : Fn(usize , &T) -> u64 {    
@@ -198,8 +182,8 @@ where
             break;
         }
 
-        let mut moves_for_piece = generation_function(index , &args) & (!ally.occupied);
-        iterate_move_map(&enemy, index, &mut moves_for_piece, mode , moves);
+        let mut moves_for_piece = generation_function(index , &args) & targets;
+        iterate_move_map(enemy, index, &mut moves_for_piece, mode , moves);
 
         flip_bit(&mut piece_positions, index);
     }
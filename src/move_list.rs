@@ -0,0 +1,70 @@
+use std::ops::{Deref, DerefMut};
+
+// no legal position has more than 218 moves, pseudo-legal ones included this stays under 256
+pub const MAX_MOVES : usize = 256;
+
+// moves generated for one position, kept on the stack
+#[derive(Clone)]
+pub struct MoveList {
+    moves : [u16 ; MAX_MOVES],
+    len : usize,
+}
+
+impl MoveList {
+    pub fn new() -> MoveList {
+        MoveList { moves : [0 ; MAX_MOVES], len : 0 }
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self , mov : u16) {
+        self.moves[self.len] = mov;
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn extend_from(&mut self , other : &MoveList) {
+        self.moves[self.len..self.len + other.len].copy_from_slice(other);
+        self.len += other.len;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        MoveList::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [u16];
+
+    fn deref(&self) -> &[u16] {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [u16] {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = u16;
+    type IntoIter = std::iter::Take<std::array::IntoIter<u16, MAX_MOVES>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.moves.into_iter().take(self.len)
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a u16;
+    type IntoIter = std::slice::Iter<'a, u16>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -0,0 +1,173 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+
+use crate::{attack_maps::LINE, bit_board::BitBoard, move_list::MoveList, player::Player, utils::get_lsb};
+
+
+const EN_PESSANT : u16 = 5;
+
+
+// shared by every thread without a lock: a slot holds `key ^ data` beside `data`,
+// so a slot torn by two writers no longer matches its key and reads as a miss
+pub struct PerftHashTable {
+    entries : Vec<[AtomicU64 ; 2]>,
+    mask : usize,
+}
+
+impl PerftHashTable {
+    // `megabytes` is rounded down to a power of two number of slots
+    pub fn new(megabytes : usize) -> Self {
+        let slots = ((megabytes << 20) / 16).max(1);
+        let size = if slots.is_power_of_two() { slots } else { slots.next_power_of_two() >> 1 };
+        PerftHashTable {
+            entries : (0..size).map(|_| [AtomicU64::new(0), AtomicU64::new(0)]).collect(),
+            mask : size - 1,
+        }
+    }
+
+    // the node count sits above an 8 bit depth
+    fn probe(&self , key : u64 , depth : usize) -> Option<u64> {
+        let entry = &self.entries[key as usize & self.mask];
+        let data = entry[1].load(Ordering::Relaxed);
+        let check = entry[0].load(Ordering::Relaxed);
+        (check ^ data == key && data & 0xFF == depth as u64).then_some(data >> 8)
+    }
+
+    fn store(&self , key : u64 , depth : usize , nodes : u64) {
+        let entry = &self.entries[key as usize & self.mask];
+        let data = nodes << 8 | depth as u64;
+        entry[0].store(key ^ data, Ordering::Relaxed);
+        entry[1].store(data, Ordering::Relaxed);
+    }
+}
+
+
+pub struct Perft {
+    // count the legal moves one ply from the leaves instead of playing them
+    pub bulk : bool,
+    pub threads : usize,
+    pub hash : Option<PerftHashTable>,
+}
+
+impl Perft {
+    pub fn new() -> Perft {
+        Perft { bulk : true, threads : 1, hash : None }
+    }
+
+    pub fn count(&self , board : &BitBoard , depth : usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        self.divide(board, depth).iter().map(|(_, nodes)| nodes).sum()
+    }
+
+    // leaf count below every legal root move, in generation order; the root moves are shared out between threads
+    pub fn divide(&self , board : &BitBoard , depth : usize) -> Vec<(u16, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        // the game that led here is not needed below the root, and every thread would copy it
+        let mut board = board.clone();
+        board.history.clear();
+
+        let root_moves = board.legal_moves();
+        let next = AtomicUsize::new(0);
+        let mut counts = vec![0u64 ; root_moves.len()];
+
+        thread::scope(|scope| {
+            let workers : Vec<_> = (0..self.threads.clamp(1, root_moves.len().max(1))).map(|_| {
+                let mut board = board.clone();
+                let (root_moves, next) = (&root_moves, &next);
+                scope.spawn(move || {
+                    let mut counted = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= root_moves.len() {
+                            break counted;
+                        }
+                        board.make_move(root_moves[index]);
+                        counted.push((index, self.search(&mut board, depth - 1)));
+                        board.unmake_move();
+                    }
+                })
+            }).collect();
+
+            for worker in workers {
+                for (index, nodes) in worker.join().unwrap() {
+                    counts[index] = nodes;
+                }
+            }
+        });
+
+        root_moves.iter().copied().zip(counts).collect()
+    }
+
+    fn search(&self , board : &mut BitBoard , depth : usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let key = board.key();
+        if let Some(nodes) = self.hash.as_ref().and_then(|hash| hash.probe(key, depth)) {
+            return nodes;
+        }
+
+        let turn = board.player;
+        board.generate_attack_maps(turn);
+        board.generate_attack_maps(!turn);
+        let moves = board.generate_moves(turn);
+
+        let nodes = if depth == 1 && self.bulk {
+            count_legal(board, &moves)
+        } else {
+            let mut nodes = 0;
+            for mov in moves {
+                board.make_move(mov);
+                if !board.king_in_check(turn) {
+                    nodes += self.search(board, depth - 1);
+                }
+                board.unmake_move();
+            }
+            nodes
+        };
+
+        if let Some(hash) = &self.hash {
+            hash.store(key, depth, nodes);
+        }
+        nodes
+    }
+}
+
+impl Default for Perft {
+    fn default() -> Self {
+        Perft::new()
+    }
+}
+
+
+// legal moves among the pseudo-legal `moves`, playing only those the pins cannot settle:
+// king moves, castling, en passant and everything while in check
+fn count_legal(board : &mut BitBoard , moves : &MoveList) -> u64 {
+    let turn = board.player;
+    let king_index = get_lsb(if turn == Player::White { board.white_set.kings } else { board.black_set.kings });
+    let in_check = board.checkers(turn) != 0;
+    let pinned = board.pinned(turn);
+
+    let mut count = 0;
+    for &mov in moves {
+        let src = mov as usize & 0x3F;
+        let dest = (mov as usize >> 6) & 0x3F;
+        let special = mov >> 12;
+
+        let legal = if in_check || src == king_index || special == EN_PESSANT {
+            board.make_move(mov);
+            let legal = !board.king_in_check(turn);
+            board.unmake_move();
+            legal
+        } else {
+            pinned & (1u64 << src) == 0 || LINE[src][king_index] & (1u64 << dest) != 0
+        };
+        count += legal as u64;
+    }
+    count
+}
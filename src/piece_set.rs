@@ -32,7 +32,7 @@ impl PieceSet
             castle_rooks : 0,
             attack_map : 0,
         };
-        return piece_set;
+        piece_set
     }
     
     pub fn get_starting_white_set() -> Self {
@@ -64,7 +64,25 @@ impl PieceSet
     
         set
     }
-    
+
+    // the same set with `transform` applied to every bitboard
+    pub fn map_bitboards<F>(&self, transform : F) -> PieceSet
+    where
+        F : Fn(u64) -> u64
+    {
+        PieceSet {
+            rooks : transform(self.rooks),
+            knights : transform(self.knights),
+            bishops : transform(self.bishops),
+            queens : transform(self.queens),
+            kings : transform(self.kings),
+            pawns : transform(self.pawns),
+            occupied : transform(self.occupied),
+            double_push_pawns : transform(self.double_push_pawns),
+            castle_rooks : transform(self.castle_rooks),
+            attack_map : transform(self.attack_map),
+        }
+    }
 
 }
 
@@ -2,7 +2,7 @@
 
 use std::ops::Not;
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Player
 {
     White,
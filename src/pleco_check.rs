@@ -0,0 +1,109 @@
+use std::fmt;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{bit_board::BitBoard, perft::Perft, player::Player, utils::move_to_uci};
+
+
+// a position where our legal moves and pleco's differ
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Mismatch {
+    pub fen : String,
+    // moves pleco generates and we do not
+    pub missing : Vec<String>,
+    // moves we generate and pleco does not
+    pub extra : Vec<String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move generation differs from pleco in {}", self.fen)?;
+        if !self.missing.is_empty() {
+            write!(f, "\n  missing: {}", self.missing.join(" "))?;
+        }
+        if !self.extra.is_empty() {
+            write!(f, "\n  extra: {}", self.extra.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+
+fn fen_of(board : &BitBoard) -> String {
+    board.bitboard_to_fen(board.player == Player::Black)
+}
+
+fn pleco_board(board : &BitBoard) -> Result<pleco::Board, String> {
+    let fen = fen_of(board);
+    pleco::Board::from_fen(&fen).map_err(|e| format!("pleco rejected {}: {:?}", fen, e))
+}
+
+// compares the legal moves of one position, in UCI notation; pleco only plays standard chess
+pub fn compare_position(board : &mut BitBoard) -> Result<Option<Mismatch>, String> {
+    let mut ours : Vec<String> = board.legal_moves().into_iter().map(|mov| move_to_uci(mov, false)).collect();
+    let mut theirs : Vec<String> = pleco_board(board)?.generate_moves().iter().map(|mov| mov.stringify()).collect();
+    ours.sort();
+    theirs.sort();
+
+    if ours == theirs {
+        return Ok(None);
+    }
+    Ok(Some(Mismatch {
+        fen : fen_of(board),
+        missing : theirs.iter().filter(|mov| !ours.contains(mov)).cloned().collect(),
+        extra : ours.iter().filter(|mov| !theirs.contains(mov)).cloned().collect(),
+    }))
+}
+
+// walks down the first root move whose perft count disagrees with pleco's until the move lists
+// themselves differ, so a count mismatch ends in the exact position and move
+pub fn find_divergence(board : &mut BitBoard , depth : usize) -> Result<Option<Mismatch>, String> {
+    if let Some(mismatch) = compare_position(board)? {
+        return Ok(Some(mismatch));
+    }
+    if depth <= 1 {
+        return Ok(None);
+    }
+
+    let perft = Perft::new();
+    for mov in board.legal_moves() {
+        board.make_move(mov);
+        let ours = perft.count(board, depth - 1);
+        let theirs = pleco::board::perft::perft(&pleco_board(board)?, (depth - 1) as u16);
+        let divergence = if ours != theirs { find_divergence(board, depth - 1)? } else { None };
+        board.unmake_move();
+
+        if divergence.is_some() {
+            return Ok(divergence);
+        }
+        if ours != theirs {
+            return Err(format!("perft {} after {} in {} is {} against pleco's {} but no position differs",
+                depth - 1, move_to_uci(mov, false), fen_of(board), ours, theirs));
+        }
+    }
+    Ok(None)
+}
+
+// plays `games` random games of up to `plies` plies, comparing every position reached and, when
+// `depth` is above one, the perft counts below it; returns the number of positions checked
+pub fn check_random_games(games : usize , plies : usize , depth : usize , seed : u64) -> Result<usize, String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut checked = 0;
+
+    for _ in 0..games {
+        let mut board = BitBoard::get_starting_board();
+        for _ in 0..plies {
+            if let Some(mismatch) = find_divergence(&mut board, depth)? {
+                return Err(mismatch.to_string());
+            }
+            checked += 1;
+
+            let moves = board.legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            board.make_move(moves[rng.random_range(0..moves.len())]);
+        }
+    }
+    Ok(checked)
+}
@@ -0,0 +1,60 @@
+use serde_json::json;
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{bit_board::BitBoard, evaluation::{params::EvalParams, Evaluator}};
+
+
+// every route answers with JSON:
+//   POST /evaluate   body: a FEN, the score of the side to move with the evaluation in use
+//   POST /trace      body: a FEN, the handcrafted evaluation term by term
+//   GET  /params     the evaluation parameters in use
+//   PUT  /params     body: parameters as JSON, fields left out keep their defaults
+pub fn handle(evaluator : &mut Evaluator , method : &str , path : &str , body : &str) -> (u16, String) {
+    let result = match (method, path) {
+        ("POST", "/evaluate") => BitBoard::fen_to_valid_bitboard(body.trim())
+            .map(|board| json!({ "eval" : evaluator.evaluate(&board), "nnue" : evaluator.uses_nnue() }).to_string()),
+        ("POST", "/trace") => BitBoard::fen_to_valid_bitboard(body.trim()).and_then(|board| {
+            serde_json::to_string(&evaluator.trace(&board)).map_err(|e| format!("Failed to serialize trace: {}", e))
+        }),
+        ("GET", "/params") => serde_json::to_string(evaluator.params())
+            .map_err(|e| format!("Failed to serialize parameters: {}", e)),
+        ("PUT", "/params") => EvalParams::from_json(body).map(|params| {
+            evaluator.set_params(params);
+            json!({ "ok" : true }).to_string()
+        }),
+        _ => return (404, json!({ "error" : format!("No route for {} {}", method, path) }).to_string()),
+    };
+
+    match result {
+        Ok(json) => (200, json),
+        Err(e) => (400, json!({ "error" : e }).to_string()),
+    }
+}
+
+// answers requests one at a time until the process is stopped
+pub fn serve(address : &str , mut evaluator : Evaluator) -> Result<(), String> {
+    let server = Server::http(address).map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+        let (status, json) = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => {
+                let method = match request.method() {
+                    Method::Get => "GET",
+                    Method::Post => "POST",
+                    Method::Put => "PUT",
+                    _ => "",
+                };
+                handle(&mut evaluator, method, request.url(), &body)
+            }
+            Err(e) => (400, json!({ "error" : format!("Failed to read request: {}", e) }).to_string()),
+        };
+
+        let response = Response::from_string(json).with_status_code(status).with_header(content_type.clone());
+        if let Err(e) = request.respond(response) {
+            eprintln!("Failed to answer request: {}", e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,660 @@
+use crate::bit_board::BitBoard;
+use crate::evaluation::pawns::*;
+use crate::evaluation::king_safety::evaluate_king_safety;
+use crate::evaluation::material::*;
+use crate::evaluation::mobility::*;
+use crate::evaluation::nnue::*;
+use crate::evaluation::nnue_training::*;
+use crate::evaluation::params::*;
+use crate::evaluation::{evaluate, evaluate_trace, evaluate_with_params, Evaluator, Score, BISHOP_VALUE, PAWN_VALUE, TEMPO};
+use crate::player::Player;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{square, squares};
+
+#[test]
+fn test_starting_position_is_balanced() {
+    let board = BitBoard::get_starting_board();
+    let entry = evaluate_pawn_structure(board.white_set.pawns, board.black_set.pawns, board.pawn_key(), &DEFAULT_PARAMS);
+
+    assert_eq!(entry.scores[0], entry.scores[1]);
+    assert_eq!(entry.passed, [0, 0]);
+    // only the side-to-move bonus is left, blended at full phase
+    assert_eq!(evaluate(&board), TEMPO.mg);
+}
+
+#[test]
+fn test_pawn_structure_patterns() {
+    // white: a2 isolated, c3/c4 doubled, d5 passed and defended, e4 held back by f7
+    // black: f7-g6-h7 chain with nothing in front of g6 and h7
+    let board = BitBoard::fen_to_bitboard("4k3/5p1p/6p1/3P4/2P1P3/2P5/P7/4K3 w - - 0 1").unwrap();
+    let white = board.white_set.pawns;
+    let black = board.black_set.pawns;
+
+    assert_eq!(isolated_pawns(white), square("a2"));
+    assert_eq!(doubled_pawns(white, Player::White), square("c4"));
+    assert_eq!(passed_pawns(white, black, Player::White), squares(&["a2", "c4", "d5"]));
+    assert_eq!(connected_pawns(white, Player::White), squares(&["d5"]));
+    assert_eq!(connected_pawns(black, Player::Black), squares(&["g6"]));
+    assert_eq!(passed_pawns(black, white, Player::Black), squares(&["g6", "h7"]));
+    assert_eq!(pawn_islands(white), 2);
+    assert_eq!(pawn_islands(black), 1);
+}
+
+#[test]
+fn test_backward_and_candidate_pawns() {
+    // d4 is covered by e5 and c4 has already gone past the point where it could defend it
+    let board = BitBoard::fen_to_bitboard("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(backward_pawns(board.white_set.pawns, board.black_set.pawns, Player::White), square("d3"));
+
+    // b4 faces a single sentry on a5 and is helped by c3
+    let board = BitBoard::fen_to_bitboard("4k3/8/8/p7/1P6/2P5/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(candidate_pawns(board.white_set.pawns, board.black_set.pawns, Player::White), square("b4"));
+}
+
+#[test]
+fn test_blocked_passed_pawn_scores_less() {
+    let free = BitBoard::fen_to_bitboard("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+    let blocked = BitBoard::fen_to_bitboard("4k3/8/3n4/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+
+    let free_entry = evaluate_pawn_structure(free.white_set.pawns, free.black_set.pawns, free.pawn_key(), &DEFAULT_PARAMS);
+    let blocked_entry = evaluate_pawn_structure(blocked.white_set.pawns, blocked.black_set.pawns, blocked.pawn_key(), &DEFAULT_PARAMS);
+
+    assert_eq!(evaluate_passed_pawns(&free, &free_entry, Player::White, &DEFAULT_PARAMS), PASSED_PAWN[4]);
+    assert_eq!(evaluate_passed_pawns(&blocked, &blocked_entry, Player::White, &DEFAULT_PARAMS), BLOCKED_PASSED_PAWN[4]);
+}
+
+#[test]
+fn test_pawn_hash_table_matches_direct_evaluation() {
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+    let mut evaluator = Evaluator::new();
+    let mut table = PawnHashTable::new(16);
+
+    for _ in 0..2 {
+        for fen in fens {
+            let board = BitBoard::fen_to_bitboard(fen).unwrap();
+            let direct = evaluate_pawn_structure(board.white_set.pawns, board.black_set.pawns, board.pawn_key(), &DEFAULT_PARAMS);
+            let cached = table.probe(&board, &DEFAULT_PARAMS);
+
+            assert_eq!(cached.key, direct.key);
+            assert_eq!(cached.scores, direct.scores);
+            assert_eq!(cached.passed, direct.passed);
+            assert_eq!(evaluator.evaluate(&board), evaluate(&board));
+        }
+    }
+}
+
+#[test]
+fn test_pawn_hash_follows_apply_and_unapply() {
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 b kq - 0 1",
+    ];
+
+    for fen in fens {
+        let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+        let turn = board.player;
+        let before = board.pawn_hash;
+        for mov in board.generate_moves(turn) {
+            let mov_result = board.apply_move(turn, mov);
+            assert_eq!(board.pawn_hash, board.pawn_key(), "{} {:#06x}", fen, mov);
+            board.unapply_move(turn, mov, mov_result);
+            assert_eq!(board.pawn_hash, before, "{} {:#06x}", fen, mov);
+        }
+    }
+}
+
+#[test]
+fn test_king_safety_is_symmetric_in_starting_position() {
+    let board = BitBoard::get_starting_board();
+    assert_eq!(evaluate_king_safety(&board, Player::White, &DEFAULT_PARAMS), evaluate_king_safety(&board, Player::Black, &DEFAULT_PARAMS));
+}
+
+#[test]
+fn test_king_safety_pawn_shield_and_open_files() {
+    let sheltered = BitBoard::fen_to_bitboard("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+    let stripped = BitBoard::fen_to_bitboard("6k1/5ppp/8/8/8/8/8/6K1 w - - 0 1").unwrap();
+    let stormed = BitBoard::fen_to_bitboard("6k1/5p1p/8/8/8/6p1/5PPP/6K1 w - - 0 1").unwrap();
+
+    let sheltered_score = evaluate_king_safety(&sheltered, Player::White, &DEFAULT_PARAMS);
+    assert_eq!(sheltered_score, evaluate_king_safety(&sheltered, Player::Black, &DEFAULT_PARAMS));
+    assert!(evaluate_king_safety(&stripped, Player::White, &DEFAULT_PARAMS).mg < sheltered_score.mg);
+    assert!(evaluate_king_safety(&stormed, Player::White, &DEFAULT_PARAMS).mg < sheltered_score.mg);
+}
+
+#[test]
+fn test_king_danger_grows_with_attackers_and_safe_checks() {
+    let quiet = BitBoard::fen_to_bitboard("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+    let attacked = BitBoard::fen_to_bitboard("6k1/5ppp/8/8/6nq/8/5PPP/6K1 w - - 0 1").unwrap();
+    // the a8 rook can check along the open first rank, unless the d1 rook is in the way
+    let checkable = BitBoard::fen_to_bitboard("r5k1/5ppp/8/8/6nq/8/5PPP/6K1 w - - 0 1").unwrap();
+    let covered = BitBoard::fen_to_bitboard("r5k1/5ppp/8/8/6nq/8/5PPP/3R2K1 w - - 0 1").unwrap();
+
+    let quiet_score = evaluate_king_safety(&quiet, Player::White, &DEFAULT_PARAMS);
+    let attacked_score = evaluate_king_safety(&attacked, Player::White, &DEFAULT_PARAMS);
+    let checkable_score = evaluate_king_safety(&checkable, Player::White, &DEFAULT_PARAMS);
+    let covered_score = evaluate_king_safety(&covered, Player::White, &DEFAULT_PARAMS);
+
+    assert!(attacked_score.mg < quiet_score.mg);
+    assert!(checkable_score.mg < attacked_score.mg);
+    assert_eq!(covered_score, attacked_score);
+}
+
+#[test]
+fn test_mobility_area_and_counts() {
+    // e5 is covered by the d6 pawn and d2 is a blocked own pawn
+    let board = BitBoard::fen_to_bitboard("4k3/8/3p4/8/8/3N4/3P4/4K3 w - - 0 1").unwrap();
+    let area = mobility_area(&board, Player::White);
+    assert_eq!(area & square("e5"), 0);
+    assert_eq!(area & square("c5"), 0);
+    assert_eq!(area & square("d2"), 0);
+    assert_eq!(blocked_pawns(&board, Player::White), square("d2"));
+
+    let central = BitBoard::fen_to_bitboard("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+    let cornered = BitBoard::fen_to_bitboard("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+    assert_eq!(evaluate_mobility(&central, Player::White, &DEFAULT_PARAMS), KNIGHT_MOBILITY[8]);
+    assert_eq!(evaluate_mobility(&cornered, Player::White, &DEFAULT_PARAMS), KNIGHT_MOBILITY[2]);
+    assert_eq!(evaluate_mobility(&central, Player::Black, &DEFAULT_PARAMS), Score::default());
+}
+
+#[test]
+fn test_rook_files_and_seventh_rank() {
+    let open = BitBoard::fen_to_bitboard("4k3/p7/8/8/8/8/8/3RK3 w - - 0 1").unwrap();
+    let semi_open = BitBoard::fen_to_bitboard("3pk3/8/8/8/8/8/8/3RK3 w - - 0 1").unwrap();
+    let closed = BitBoard::fen_to_bitboard("4k3/8/8/8/8/8/3P4/3RK3 w - - 0 1").unwrap();
+    let seventh = BitBoard::fen_to_bitboard("4k3/p2R4/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+
+    assert_eq!(evaluate_piece_placement(&open, Player::White, &DEFAULT_PARAMS), ROOK_OPEN_FILE);
+    assert_eq!(evaluate_piece_placement(&semi_open, Player::White, &DEFAULT_PARAMS), ROOK_SEMI_OPEN_FILE);
+    assert_eq!(evaluate_piece_placement(&closed, Player::White, &DEFAULT_PARAMS), Score::default());
+    assert_eq!(evaluate_piece_placement(&seventh, Player::White, &DEFAULT_PARAMS), ROOK_OPEN_FILE + ROOK_ON_SEVENTH);
+}
+
+#[test]
+fn test_outposts_bad_and_trapped_bishops() {
+    // d5 is supported by e4 and no black pawn can ever challenge it
+    let outpost = BitBoard::fen_to_bitboard("4k3/7p/8/3N4/4P3/8/8/4K3 w - - 0 1").unwrap();
+    let challenged = BitBoard::fen_to_bitboard("4k3/2p4p/8/3N4/4P3/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(evaluate_piece_placement(&outpost, Player::White, &DEFAULT_PARAMS), KNIGHT_OUTPOST);
+    assert_eq!(evaluate_piece_placement(&challenged, Player::White, &DEFAULT_PARAMS), Score::default());
+
+    // a blocked pawn on the other colour does not hurt, one on the bishop's colour counts twice
+    let bad_bishop = BitBoard::fen_to_bitboard("4k3/8/8/8/3p4/3P4/8/2B1K3 w - - 0 1").unwrap();
+    assert_eq!(evaluate_piece_placement(&bad_bishop, Player::White, &DEFAULT_PARAMS), Score::default());
+    let bad_bishop = BitBoard::fen_to_bitboard("4k3/8/8/8/4p3/4P3/8/2B1K3 w - - 0 1").unwrap();
+    assert_eq!(evaluate_piece_placement(&bad_bishop, Player::White, &DEFAULT_PARAMS), BAD_BISHOP_PAWN * 2);
+
+    let trapped = BitBoard::fen_to_bitboard("4k3/8/8/8/8/1P6/b7/4K3 b - - 0 1").unwrap();
+    assert_eq!(evaluate_piece_placement(&trapped, Player::Black, &DEFAULT_PARAMS), TRAPPED_BISHOP);
+}
+
+#[test]
+fn test_trapped_rook_next_to_uncastled_king() {
+    let trapped = BitBoard::fen_to_bitboard("4k3/8/8/8/8/8/5PPP/5K1R w - - 0 1").unwrap();
+    let free = BitBoard::fen_to_bitboard("4k3/8/8/8/8/8/5PP1/5K1R w - - 0 1").unwrap();
+    assert_eq!(evaluate_piece_placement(&trapped, Player::White, &DEFAULT_PARAMS), TRAPPED_ROOK);
+    assert_eq!(evaluate_piece_placement(&free, Player::White, &DEFAULT_PARAMS), ROOK_OPEN_FILE);
+}
+
+#[test]
+fn test_material_signature_and_imbalance() {
+    let board = BitBoard::get_starting_board();
+    let signature = MaterialSignature::from_board(&board);
+    assert_eq!(signature.counts, [[8, 2, 2, 2, 1], [8, 2, 2, 2, 1]]);
+    assert_eq!(signature.non_pawn_material(Player::White), 2 * 320 + 2 * 330 + 2 * 500 + 950);
+
+    let entry = evaluate_material_signature(signature, &DEFAULT_PARAMS);
+    assert_eq!(entry.imbalance[0], entry.imbalance[1]);
+    assert_eq!(
+        entry.imbalance[0],
+        BISHOP_PAIR + REDUNDANT_ROOK + REDUNDANT_QUEEN + KNIGHT_PAWN_ADJUSTMENT * 6
+    );
+
+    // trading the bishop pair away for a knight pair
+    let knights = BitBoard::fen_to_bitboard("rn1qk1nr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    let signature = MaterialSignature::from_board(&knights);
+    assert_ne!(signature.key(), MaterialSignature::from_board(&board).key());
+    assert_eq!(
+        evaluate_imbalance(&signature, Player::White, &DEFAULT_PARAMS) - evaluate_imbalance(&signature, Player::Black, &DEFAULT_PARAMS),
+        BISHOP_PAIR
+    );
+
+    let mut table = MaterialHashTable::new(8);
+    for _ in 0..2 {
+        let cached = *table.probe(&knights, &DEFAULT_PARAMS);
+        assert_eq!(cached.key, signature.key());
+        assert_eq!(cached.imbalance, evaluate_material_signature(signature, &DEFAULT_PARAMS).imbalance);
+    }
+}
+
+fn scale_for(fen : &str , strong : Player) -> i32 {
+    let board = BitBoard::fen_to_bitboard(fen).unwrap();
+    let entry = evaluate_material_signature(MaterialSignature::from_board(&board), &DEFAULT_PARAMS);
+    scale_factor(&board, &entry.signature, entry.scale_rules[strong as usize], strong, &DEFAULT_PARAMS)
+}
+
+#[test]
+fn test_endgame_scale_factors() {
+    // opposite-coloured bishops, then same-coloured ones
+    assert_eq!(scale_for("4k3/8/2b5/8/8/4B3/PP6/4K3 w - - 0 1", Player::White), SCALE_OPPOSITE_BISHOPS);
+    assert_eq!(scale_for("4k3/8/2b5/8/8/4B3/PP6/R3K3 w - - 0 1", Player::White), SCALE_OPPOSITE_BISHOPS_WITH_PIECES);
+    assert_eq!(scale_for("4k3/8/3b4/8/8/4B3/PP6/4K3 w - - 0 1", Player::White), SCALE_NORMAL);
+
+    // rook pawn with a bishop that does not control the queening corner
+    assert_eq!(scale_for("1k6/8/8/P7/8/8/8/2B1K3 w - - 0 1", Player::White), SCALE_DRAW);
+    assert_eq!(scale_for("1k6/8/8/P7/8/8/8/3BK3 w - - 0 1", Player::White), SCALE_NORMAL);
+    assert_eq!(scale_for("8/8/8/P7/8/8/5k2/2B1K3 w - - 0 1", Player::White), SCALE_NORMAL);
+    assert_eq!(scale_for("4k3/2b5/8/8/8/8/7p/6K1 b - - 0 1", Player::Black), SCALE_DRAW);
+
+    // pawnless endings a minor piece up
+    assert_eq!(scale_for("4k3/8/8/8/8/8/8/3NK3 w - - 0 1", Player::White), SCALE_DRAW);
+    assert_eq!(scale_for("4k3/8/8/3b4/8/8/8/3RK3 w - - 0 1", Player::White), SCALE_PAWNLESS_EXCHANGE_UP);
+    assert_eq!(scale_for("4k3/8/8/8/8/8/8/3RK3 w - - 0 1", Player::White), SCALE_NORMAL);
+}
+
+fn random_network(feature_set : FeatureSet , hidden_size : usize , seed : u64) -> Network {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let features = feature_set.num_features();
+    Network {
+        feature_set,
+        hidden_size,
+        feature_weights : (0..features * hidden_size).map(|_| rng.random_range(-64..64)).collect(),
+        feature_biases : (0..hidden_size).map(|_| rng.random_range(0..128)).collect(),
+        output_weights : (0..2 * hidden_size).map(|_| rng.random_range(-128..128)).collect(),
+        output_bias : rng.random_range(-1000..1000),
+    }
+}
+
+// walks every legal line to `depth`, checking the incremental accumulator against a fresh one
+fn check_accumulators(board : &mut BitBoard , nnue : &mut NnueState , turn : Player , depth : usize) {
+    let fresh = NnueState::new(nnue.network.clone(), board);
+    assert_eq!(nnue.accumulator(), fresh.accumulator());
+    if depth == 0 {
+        return;
+    }
+
+    board.generate_attack_maps(turn);
+    board.generate_attack_maps(!turn);
+    for mov in board.generate_moves(turn) {
+        let mov_result = nnue.apply_move(board, turn, mov);
+        if !board.king_in_check(turn) {
+            board.player = !turn;
+            check_accumulators(board, nnue, !turn, depth - 1);
+            board.player = turn;
+        }
+        nnue.unapply_move(board, turn, mov, mov_result);
+        board.generate_attack_maps(turn);
+        board.generate_attack_maps(!turn);
+    }
+}
+
+#[test]
+fn test_nnue_network_round_trip() {
+    let network = random_network(FeatureSet::HalfKA, 8, 1);
+    let bytes = network.to_bytes();
+    assert_eq!(Network::from_bytes(&bytes), Ok(network));
+
+    assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    assert!(Network::from_bytes(&bad_magic).is_err());
+}
+
+#[test]
+fn test_nnue_simd_matches_scalar() {
+    let mut rng = StdRng::seed_from_u64(2);
+    // 37 is not a multiple of the vector width, so the tail is exercised too
+    let accumulator : Vec<i16> = (0..37).map(|_| rng.random_range(-400..400)).collect();
+    let row : Vec<i16> = (0..37).map(|_| rng.random_range(-400..400)).collect();
+
+    let simd = Simd::detect();
+    assert_eq!(simd.crelu_dot(&accumulator, &row), scalar_crelu_dot(&accumulator, &row));
+
+    let (mut fast, mut slow) = (accumulator.clone(), accumulator.clone());
+    simd.add_row(&mut fast, &row);
+    scalar_add_row(&mut slow, &row);
+    assert_eq!(fast, slow);
+    simd.sub_row(&mut fast, &row);
+    scalar_sub_row(&mut slow, &row);
+    assert_eq!(fast, accumulator);
+    assert_eq!(slow, accumulator);
+}
+
+#[test]
+fn test_nnue_incremental_updates_match_refresh() {
+    // castling both ways, en passant and promotions with captures
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+
+    for feature_set in [FeatureSet::HalfKP, FeatureSet::HalfKA] {
+        for fen in fens {
+            let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+            let mut nnue = NnueState::new(random_network(feature_set, 20, 3), &board);
+            let turn = board.player;
+            check_accumulators(&mut board, &mut nnue, turn, 2);
+        }
+    }
+}
+
+#[test]
+fn test_nnue_follows_moves_made_without_it() {
+    let network = random_network(FeatureSet::HalfKA, 16, 5);
+    let fresh = |board : &BitBoard| NnueState::new(network.clone(), board).evaluate(board);
+
+    let mut board = BitBoard::fen_to_bitboard("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    let mut nnue = NnueState::new(network.clone(), &board);
+    let root = nnue.evaluate(&board);
+    let turn = board.player;
+
+    for mov in board.generate_moves(turn) {
+        // played on the board alone, the position is refreshed
+        let mov_result = board.apply_move(turn, mov);
+        assert_eq!(nnue.evaluate(&board), fresh(&board), "{:#06x}", mov);
+        board.unapply_move(turn, mov, mov_result);
+        assert_eq!(nnue.evaluate(&board), root, "{:#06x}", mov);
+
+        // taken back on the board alone, the state drops to the accumulator it already has
+        let mov_result = nnue.apply_move(&mut board, turn, mov);
+        board.unapply_move(turn, mov, mov_result);
+        assert_eq!(nnue.evaluate(&board), root, "{:#06x}", mov);
+    }
+
+    // an unrelated position is refreshed
+    let other = BitBoard::fen_to_bitboard("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+    assert_eq!(nnue.evaluate(&other), fresh(&other));
+}
+
+#[test]
+fn test_nnue_follows_make_and_unmake_move() {
+    let network = random_network(FeatureSet::HalfKA, 16, 5);
+    let fresh = |board : &BitBoard| NnueState::new(network.clone(), board).evaluate(board);
+
+    let mut board = BitBoard::fen_to_bitboard("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    let mut nnue = NnueState::new(network.clone(), &board);
+
+    // one ply at a time, several plies between evaluations, and back again
+    let mut played = 0;
+    for plies in [1, 1, 3, 2, 4] {
+        for _ in 0..plies {
+            let mov = board.legal_moves()[0];
+            board.make_move(mov);
+            played += 1;
+        }
+        assert_eq!(nnue.evaluate(&board), fresh(&board), "{}", board.bitboard_to_fen(board.player == Player::Black));
+    }
+    for plies in [1, 4, 2] {
+        for _ in 0..plies {
+            board.unmake_move();
+            played -= 1;
+        }
+        assert_eq!(nnue.evaluate(&board), fresh(&board), "{}", board.bitboard_to_fen(board.player == Player::Black));
+    }
+    assert!(played > 0);
+}
+
+#[test]
+fn test_evaluator_switches_between_nnue_and_handcrafted() {
+    let board = BitBoard::get_starting_board();
+    let mut evaluator = Evaluator::new();
+    let handcrafted = evaluator.evaluate(&board);
+
+    evaluator.set_network(random_network(FeatureSet::HalfKP, 16, 4), &board);
+    assert!(evaluator.uses_nnue());
+    let nnue = evaluator.nnue_mut().unwrap().evaluate(&board);
+    assert_eq!(evaluator.evaluate(&board), nnue);
+
+    evaluator.set_use_nnue(false);
+    assert_eq!(evaluator.evaluate(&board), handcrafted);
+}
+
+// positions from a few random games, labelled with the handcrafted evaluation
+fn training_positions(feature_set : FeatureSet , count : usize) -> Vec<TrainingPosition> {
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut positions = Vec::new();
+
+    while positions.len() < count {
+        let mut board = BitBoard::get_starting_board();
+        let mut turn = Player::White;
+        for _ in 0..40 {
+            let legal = board.legal_moves();
+            if legal.is_empty() {
+                break;
+            }
+            board.apply_move(turn, legal[rng.random_range(0..legal.len())]);
+            turn = !turn;
+            board.player = turn;
+
+            let score = evaluate(&board) as f32;
+            let white_score = if turn == Player::White { score } else { -score };
+            positions.push(TrainingPosition::from_board(&board, feature_set, white_score, 0.5));
+        }
+    }
+
+    positions
+}
+
+#[test]
+fn test_nnue_training_reduces_loss_and_exports() {
+    let positions = training_positions(FeatureSet::HalfKP, 200);
+    let mut rng = StdRng::seed_from_u64(6);
+    let config = TrainerConfig { learning_rate : 0.01, lambda : 1.0, batch_size : 64, threads : 2 };
+    let mut trainer = Trainer::new(FloatNetwork::new(FeatureSet::HalfKP, 8, &mut rng), config);
+
+    let initial = trainer.loss(&positions);
+    for _ in 0..20 {
+        trainer.train_epoch(&positions, &mut rng);
+    }
+    assert!(trainer.loss(&positions) < initial * 0.5);
+
+    // the exported network scores positions like the float one, up to rounding
+    let network = Network::from_bytes(&trainer.network.quantize().to_bytes()).unwrap();
+    let board = BitBoard::fen_to_bitboard("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+    let quantized = NnueState::new(network, &board).evaluate(&board) as f32;
+    let float = trainer.network.evaluate(&TrainingPosition::from_board(&board, FeatureSet::HalfKP, 0.0, 0.5));
+    assert!((quantized - float).abs() < 10.0, "quantized {} float {}", quantized, float);
+}
+
+#[test]
+fn test_nnue_training_positions_and_checkpoints() {
+    let position = TrainingPosition::parse("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1 | 150 | 1-0", FeatureSet::HalfKP).unwrap();
+    // from black's point of view, with a pawn feature on each side's list
+    assert_eq!(position.score, -150.0);
+    assert_eq!(position.result, 0.0);
+    assert_eq!(position.features[0].len(), 1);
+    assert_eq!(position.features[1].len(), 1);
+    assert!(TrainingPosition::parse("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1 | 150", FeatureSet::HalfKP).is_err());
+
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut trainer = Trainer::new(FloatNetwork::new(FeatureSet::HalfKA, 4, &mut rng), TrainerConfig::default());
+    trainer.train_epoch(&training_positions(FeatureSet::HalfKA, 10), &mut rng);
+
+    let path = std::env::temp_dir().join(format!("nnue-checkpoint-{}.ckpt", std::process::id()));
+    let path = path.to_str().unwrap();
+    trainer.save_checkpoint(path).unwrap();
+    let restored = Trainer::load_checkpoint(path, TrainerConfig::default()).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(restored.network, trainer.network);
+    assert_eq!(restored.epoch, 1);
+}
+
+#[test]
+fn test_eval_params_vector_and_sources() {
+    let params = EvalParams::default();
+    let mut values = params.to_vector();
+    assert_eq!(EvalParams::from_vector(&values), params);
+
+    values[0] += 7;
+    let changed = EvalParams::from_vector(&values);
+    assert_eq!(changed.pawn_value, PAWN_VALUE + Score::new(7, 0));
+
+    let source = params.to_rust_source();
+    assert!(source.contains("pub const PAWN_VALUE : Score = Score::new(100, 120);"));
+    assert!(source.contains("pub const KNIGHT_SAFE_CHECK : i32 = 792;"));
+
+    let json = serde_json::to_string(&params).unwrap();
+    assert_eq!(serde_json::from_str::<EvalParams>(&json).unwrap(), params);
+}
+
+#[test]
+fn test_evaluation_follows_params() {
+    let board = BitBoard::fen_to_bitboard("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+    let mut params = EvalParams::default();
+    let before = evaluate_with_params(&board, &params);
+    params.queen_value += Score::new(40, 40);
+    assert_eq!(evaluate_with_params(&board, &params), before + 40);
+}
+
+#[test]
+fn test_texel_tuning() {
+    use crate::evaluation::tuning::*;
+
+    let position = TuningPosition::parse("4k3/8/8/8/8/8/8/3QK3 b - - c9 \"1-0\";").unwrap();
+    assert_eq!(position.result, 1.0);
+    assert!(position.board.player == Player::Black);
+    assert_eq!(TuningPosition::parse("4k3/8/8/8/8/8/8/3QK3 w - - 0 1 [0.5]").unwrap().result, 0.5);
+    assert!(TuningPosition::parse("4k3/8/8/8/8/8/8/3QK3 w - -").is_err());
+
+    // the nnue trainer reads its results through the same function
+    use crate::evaluation::dataset::parse_result;
+    assert_eq!(parse_result("0-1"), Some(0.0));
+    assert_eq!(parse_result("0.5"), Some(0.5));
+    assert_eq!(parse_result("[1.0]"), Some(1.0));
+    assert_eq!(parse_result("1.5"), None);
+
+    let positions : Vec<TuningPosition> = [
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 1/2-1/2",
+        "4k3/8/8/8/8/8/PPP5/3QK3 w - - 1-0",
+        "3qk3/ppp5/8/8/8/8/8/4K3 w - - 0-1",
+        "4k3/8/8/8/8/8/PP6/4K3 w - - 1-0",
+        "4k3/pp6/8/8/8/8/8/4K3 b - - 0-1",
+        "4k3/8/8/3p4/8/8/8/R3K3 w - - 1/2-1/2",
+    ].iter().map(|line| TuningPosition::parse(line).unwrap()).collect();
+
+    let start = EvalParams::default();
+    let k = fit_k(&positions, &start, 2);
+    assert!(k > 0.0);
+    let start_error = mean_error(&positions, &start, k, 2);
+    assert!(start_error <= mean_error(&positions, &start, k * 2.0, 2));
+
+    let config = TunerConfig { k, iterations : 1, learning_rate : 1.0, threads : 2 };
+    let mut reports = Vec::new();
+    let tuned = local_search(&positions, &start, config, &mut |iteration, error| reports.push((iteration, error)));
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].1 < start_error);
+    assert_eq!(mean_error(&positions, &tuned, k, 1), reports[0].1);
+}
+
+#[test]
+fn test_eval_params_from_partial_json() {
+    let params = EvalParams::from_json(r#"{ "tempo": { "mg": 0, "eg": 0 }, "knight_safe_check": 500 }"#).unwrap();
+    assert_eq!(params.tempo, Score::default());
+    assert_eq!(params.knight_safe_check, 500);
+    assert_eq!(params.queen_mobility, QUEEN_MOBILITY);
+
+    assert!(EvalParams::from_json(r#"{ "tempoo": { "mg": 0, "eg": 0 } }"#).is_err());
+    assert!(EvalParams::from_json(r#"{ "pawn_table": [1, 2, 3] }"#).is_err());
+}
+
+#[test]
+fn test_evaluator_params_and_options() {
+    let board = BitBoard::get_starting_board();
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate(&board), TEMPO.mg);
+
+    let path = std::env::temp_dir().join(format!("eval-params-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    let params = EvalParams { tempo : Score::new(5, 5), ..EvalParams::default() };
+    params.save(path).unwrap();
+
+    evaluator.set_option("EvalParams", path, &board).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(evaluator.params(), &params);
+    assert_eq!(evaluator.evaluate(&board), 5);
+
+    evaluator.set_option("EvalParams", "<default>", &board).unwrap();
+    assert_eq!(evaluator.evaluate(&board), TEMPO.mg);
+
+    assert!(evaluator.set_option("EvalParams", "/nonexistent/params.json", &board).is_err());
+    assert!(evaluator.set_option("UseNNUE", "maybe", &board).is_err());
+    assert!(evaluator.set_option("Hash", "16", &board).is_err());
+}
+
+#[test]
+fn test_evaluation_trace_adds_up() {
+    let fens = [
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        "4k3/8/2b5/8/8/4B3/PP6/4K3 w - - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+    ];
+    let mut evaluator = Evaluator::new();
+
+    for fen in fens {
+        let board = BitBoard::fen_to_bitboard(fen).unwrap();
+        let trace = evaluate_trace(&board);
+        assert_eq!(trace.eval, evaluate(&board));
+        assert_eq!(evaluator.trace(&board), trace);
+
+        let total = trace.terms().iter().fold(Score::default(), |total, (_, term)| total + term.total());
+        assert_eq!(total, trace.total);
+        assert_eq!(trace.scaled_eg, trace.total.eg * trace.scale_factor / SCALE_NORMAL);
+        assert_eq!(trace.blended, if board.player == Player::White { trace.eval } else { -trace.eval });
+        assert_eq!(trace.tempo.total(), if board.player == Player::White { TEMPO } else { -TEMPO });
+    }
+
+    let board = BitBoard::fen_to_bitboard(fens[2]).unwrap();
+    let trace = evaluate_trace(&board);
+    assert_eq!(trace.scale_factor, SCALE_OPPOSITE_BISHOPS);
+    assert_eq!(trace.material.white, PAWN_VALUE * 2 + BISHOP_VALUE);
+
+    let json : serde_json::Value = serde_json::to_value(&trace).unwrap();
+    assert_eq!(json["king_safety"]["white"]["mg"], trace.king_safety.white.mg);
+    assert_eq!(json["eval"], trace.eval);
+    assert!(trace.to_string().contains("King safety"));
+}
+
+#[test]
+fn test_server_routes() {
+    use crate::server::handle;
+
+    let mut evaluator = Evaluator::new();
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    let board = BitBoard::fen_to_bitboard(fen).unwrap();
+
+    let (status, body) = handle(&mut evaluator, "POST", "/trace", fen);
+    assert_eq!(status, 200);
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap(), serde_json::to_value(evaluate_trace(&board)).unwrap());
+
+    let (status, body) = handle(&mut evaluator, "POST", "/evaluate", fen);
+    assert_eq!(status, 200);
+    let json : serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["eval"], evaluate(&board));
+
+    // parameters go out and come back as JSON, and a changed set is used from then on
+    let (status, body) = handle(&mut evaluator, "GET", "/params", "");
+    assert_eq!(status, 200);
+    assert_eq!(EvalParams::from_json(&body).unwrap(), DEFAULT_PARAMS);
+
+    let mut params = DEFAULT_PARAMS;
+    params.tempo = Score::new(100, 100);
+    let (status, _) = handle(&mut evaluator, "PUT", "/params", &serde_json::to_string(&params).unwrap());
+    assert_eq!(status, 200);
+    assert_eq!(evaluator.params(), &params);
+    let (_, body) = handle(&mut evaluator, "POST", "/evaluate", fen);
+    let json : serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["eval"], evaluate_with_params(&board, &params));
+
+    assert_eq!(handle(&mut evaluator, "PUT", "/params", "{\"no_such_term\": 1}").0, 400);
+    assert_eq!(handle(&mut evaluator, "POST", "/trace", "not a fen").0, 400);
+    assert_eq!(handle(&mut evaluator, "GET", "/nowhere", "").0, 404);
+}
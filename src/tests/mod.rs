@@ -1,6 +1,20 @@
+#[cfg(test)]
+mod evaluation;
+
+// bitboard of a square given by name, e.g. "e4"
+#[cfg(test)]
+fn square(name : &str) -> u64 {
+    let bytes = name.as_bytes();
+    1u64 << ((bytes[1] - b'1') * 8 + (bytes[0] - b'a'))
+}
+
+#[cfg(test)]
+fn squares(names : &[&str]) -> u64 {
+    names.iter().fold(0, |set, name| set | square(name))
+}
 
 
-#[allow(unused)]#[cfg(test)]
+#[allow(unused, clippy::module_inception, clippy::bool_assert_comparison, clippy::useless_vec)]#[cfg(test)]
 mod tests {
     use std::collections::hash_map;
     use std::collections::HashMap;
@@ -8,11 +22,10 @@ mod tests {
     use std::hash::Hash;
     use std::vec;
 
-    use pleco::board;
-
     use super::*;
 
-    use crate::bit_board::BitBoard;
+    use crate::bit_board::{BitBoard, PieceType};
+    use crate::move_generator::*;
     use crate::piece_set::PieceSet;
     use crate::player::Player;
     use crate::utils;
@@ -22,6 +35,16 @@ mod tests {
     const CASTLE_KING: u16 = 6; // Adjust to match your actual constant value
     const CASTLE_QUEEN: u16 = 7; // Adjust to match your actual constant value
 
+    // positions from https://www.chessprogramming.org/Perft_Results
+    const PERFT_FENS : [&str ; 6] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10"
+    ];
+
     // Helper function to create a clean board with kings and rooks in castling position
     fn setup_castling_board(side: Player) -> BitBoard {
         let mut board = BitBoard::get_empty_board(); // Assuming there's a default implementation
@@ -35,6 +58,8 @@ mod tests {
             board.black_set.rooks = (1u64 << 56) | (1u64 << 63); // Rooks at a8 and h8
             board.black_set.occupied = board.black_set.kings | board.black_set.rooks;
             board.black_set.castle_rooks = board.black_set.rooks; // Mark both rooks as castling rooks
+            board.refresh_mailbox();
+            board.refresh_keys();
         
         board
     }
@@ -76,7 +101,7 @@ mod tests {
         // Setup
         let mut board = setup_castling_board(Player::White); // White castling
         let initial_board = setup_castling_board(Player::White); // Keep a copy of the initial state
-        let king_side_castle = CASTLE_KING << 12; // Create kingside castle move
+        let king_side_castle = 4 | (7 << 6) | CASTLE_KING << 12; // e1 takes h1
         
         // Save the initial castle_rooks value
         let initial_castle_rooks = board.white_set.castle_rooks;
@@ -112,7 +137,7 @@ mod tests {
         // Setup
         let mut board = setup_castling_board(Player::White); // White castling
         let initial_board = setup_castling_board(Player::White); // Keep a copy of the initial state
-        let queen_side_castle = CASTLE_QUEEN << 12; // Create queenside castle move
+        let queen_side_castle = 4 | CASTLE_QUEEN << 12; // e1 takes a1
         
         // Save the initial castle_rooks value
         let initial_castle_rooks = board.white_set.castle_rooks;
@@ -139,7 +164,7 @@ mod tests {
         // Setup
         let mut board = setup_castling_board(Player::Black); // Black castling
         let initial_board = setup_castling_board(Player::Black); // Keep a copy of the initial state
-        let king_side_castle = CASTLE_KING << 12; // Create kingside castle move
+        let king_side_castle = 60 | (63 << 6) | CASTLE_KING << 12; // e8 takes h8
         
         // Save the initial castle_rooks value
         let initial_castle_rooks = board.black_set.castle_rooks;
@@ -166,7 +191,7 @@ mod tests {
         // Setup
         let mut board = setup_castling_board(Player::Black); // Black castling
         let initial_board = setup_castling_board(Player::Black); // Keep a copy of the initial state
-        let queen_side_castle = CASTLE_QUEEN << 12; // Create queenside castle move
+        let queen_side_castle = 60 | (56 << 6) | CASTLE_QUEEN << 12; // e8 takes a8
         
         // Save the initial castle_rooks value
         let initial_castle_rooks = board.black_set.castle_rooks;
@@ -199,7 +224,7 @@ mod tests {
         let black_initial_castle_rooks = board.black_set.castle_rooks;
         
         // White kingside castle
-        let white_king_castle = CASTLE_KING << 12;
+        let white_king_castle = 4 | (7 << 6) | CASTLE_KING << 12;
         let saved_white_castle_rooks = apply_castle_move(&mut board, Player::White , white_king_castle).0;
         
         // Verify white castle_rooks is now 0
@@ -207,7 +232,7 @@ mod tests {
         assert_eq!(saved_white_castle_rooks, white_initial_castle_rooks);
         
         // Black queenside castle
-        let black_queen_castle = CASTLE_QUEEN << 12;
+        let black_queen_castle = 60 | (56 << 6) | CASTLE_QUEEN << 12;
         let saved_black_castle_rooks = apply_castle_move(&mut board, Player::Black, black_queen_castle).0;
         
         // Verify black castle_rooks is now 0
@@ -241,7 +266,7 @@ mod tests {
         let initial_board = board.clone(); // Keep a copy
         
         // Do kingside castle
-        let king_side_castle = CASTLE_KING << 12;
+        let king_side_castle = 4 | (7 << 6) | CASTLE_KING << 12;
         let saved_castle_rooks = apply_castle_move(&mut board, Player::White, king_side_castle).0;
         
         // Verify saved value
@@ -338,6 +363,20 @@ mod tests {
 
     
 
+    #[test]
+    fn test_side_to_move_from_fen(){
+        // the second field, not the halfmove clock
+        let black = BitBoard::fen_to_bitboard("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert!(black.player == Player::Black);
+        let white = BitBoard::fen_to_bitboard("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2").unwrap();
+        assert!(white.player == Player::White);
+
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+        let board = BitBoard::fen_to_bitboard(fen).unwrap();
+        assert!(board.player == Player::Black);
+        assert_eq!(board.bitboard_to_fen(board.player == Player::Black), fen);
+    }
+
     fn preft_helper(mut board :  &mut BitBoard , depth : i32 ) -> u64{
         if depth == 0 {
             return 1;
@@ -346,7 +385,14 @@ mod tests {
         board.generate_attack_maps(turn);
         board.generate_attack_maps(!turn);
 
-        let moves = board.generate_moves(turn);
+        // the union of the stages a search would generate
+        let moves = if board.king_in_check(turn) {
+            board.generate_evasions(turn)
+        } else {
+            let mut moves = board.generate_captures(turn);
+            moves.extend_from(&board.generate_quiets(turn));
+            moves
+        };
         let mut move_count = 0 ;
 
         for mov in moves {
@@ -364,16 +410,6 @@ mod tests {
 
     #[test]
     fn test_move_generation_with_perft_positions(){
-       // positions from https://www.chessprogramming.org/Perft_Results
-
-        let fen_strings = vec![
-            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
-            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
-            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
-            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
-            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
-            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10"
-        ];
 
         let depth_and_results = vec![
             (6	, 119060324	),
@@ -385,7 +421,7 @@ mod tests {
         ];
 
 
-        for (i, fen) in fen_strings.iter().enumerate() {
+        for (i, fen) in PERFT_FENS.iter().enumerate() {
             let (depth, expected_count) = depth_and_results[i];
 
             let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
@@ -400,5 +436,782 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_chess960_move_generation_with_perft_positions(){
+        // positions from https://www.chessprogramming.org/Chess960_Perft_Results
+        let positions = [
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", 8146062),
+            ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", 16253601),
+            ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", 6417013),
+            ("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9", 9183776),
+            ("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9", 34030312),
+        ];
+
+        for (fen, expected_count) in positions {
+            let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+            assert_eq!(preft_helper(&mut board, 5), expected_count, "perft 5 of {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_chess960_castling_rights_in_fen(){
+        // Shredder-FEN and X-FEN name the same rights
+        let shredder = BitBoard::fen_to_bitboard("1r2k1r1/8/8/8/8/8/8/R3K1RR w GAg - 0 1").unwrap();
+        let x_fen = BitBoard::fen_to_bitboard("1r2k1r1/8/8/8/8/8/8/R3K1RR w GQk - 0 1").unwrap();
+        assert_eq!(shredder.white_set.castle_rooks, (1u64 << 6) | 1u64);
+        assert_eq!(shredder.black_set.castle_rooks, 1u64 << 62);
+        assert!(shredder.white_set == x_fen.white_set && shredder.black_set == x_fen.black_set);
+
+        // the inner g1 rook needs its file, the outer ones keep their letters
+        assert_eq!(shredder.bitboard_to_fen(false), "1r2k1r1/8/8/8/8/8/8/R3K1RR w GQk - 0 1");
+        let standard = BitBoard::fen_to_bitboard("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(standard.bitboard_to_fen(false), "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    }
+
+    #[test]
+    fn test_castling_is_encoded_as_king_takes_rook(){
+        let mut board = BitBoard::fen_to_bitboard("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let castle = board.parse_uci_move("e1g1", false).unwrap();
+        assert_eq!(castle, 4 | (7 << 6) | CASTLE_KING << 12);
+        assert_eq!(board.parse_uci_move("e1h1", true), Some(castle));
+        assert_eq!(move_to_uci(castle, false), "e1g1");
+        assert_eq!(move_to_uci(castle, true), "e1h1");
+
+        // a king already on g1 castles without moving, only the rook jumps to f1
+        let mut board = BitBoard::fen_to_bitboard("4k3/8/8/8/8/8/8/4R1KR w H - 0 1").unwrap();
+        let castle = board.parse_uci_move("g1h1", true).unwrap();
+        board.make_move(castle);
+        assert_eq!(board.bitboard_to_fen(true), "4k3/8/8/8/8/8/8/4RRK1 b - - 1 1");
+        board.unmake_move();
+        assert_eq!(board.bitboard_to_fen(false), "4k3/8/8/8/8/8/8/4R1KR w K - 0 1");
+    }
+
+    fn without_castling(board : &BitBoard) -> BitBoard {
+        let mut board = board.clone();
+        board.white_set.castle_rooks = 0;
+        board.black_set.castle_rooks = 0;
+        board
+    }
+
+    #[test]
+    fn test_mirroring_twice_restores_the_board(){
+        for fen in PERFT_FENS {
+            let board = BitBoard::fen_to_bitboard(fen).unwrap();
+            assert!(board.mirror_vertical().mirror_vertical() == board, "vertical mirror of {}", fen);
+            assert!(board.mirror_horizontal().mirror_horizontal() == without_castling(&board), "horizontal mirror of {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_vertical_mirror_is_symmetric(){
+        for fen in PERFT_FENS {
+            let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+            let mut mirrored = board.mirror_vertical();
+
+            assert_eq!(preft_helper(&mut board, 3), preft_helper(&mut mirrored, 3), "perft of {}", fen);
+            assert_eq!(crate::evaluation::evaluate(&board), crate::evaluation::evaluate(&mirrored), "evaluation of {}", fen);
+        }
+    }
+
+    // the queen table is not left-right symmetric, so only the move counts are compared
+    #[test]
+    fn test_horizontal_mirror_is_symmetric(){
+        for fen in PERFT_FENS {
+            let mut board = without_castling(&BitBoard::fen_to_bitboard(fen).unwrap());
+            let mut mirrored = board.mirror_horizontal();
+
+            assert_eq!(preft_helper(&mut board, 3), preft_helper(&mut mirrored, 3), "perft of {}", fen);
+        }
+    }
+
+    fn mailbox_walk(board : &mut BitBoard , depth : i32) {
+        if depth == 0 {
+            return;
+        }
+        let turn = board.player;
+        board.generate_attack_maps(turn);
+        board.generate_attack_maps(!turn);
+
+        for mov in board.generate_moves(turn) {
+            let before = board.clone();
+            let mov_result = board.apply_move(turn, mov);
+            assert_eq!(board.check_mailbox(), Ok(()), "after move {} in {}", mov, before.bitboard_to_fen(turn == Player::Black));
+            if !board.king_in_check(turn) {
+                board.player = !turn;
+                mailbox_walk(board, depth - 1);
+                board.player = turn;
+            }
+            board.unapply_move(turn, mov, mov_result);
+            assert!(board.mailbox == before.mailbox, "mailbox not restored after move {}", mov);
+        }
+    }
+
+    #[test]
+    fn test_mailbox_follows_moves(){
+        for fen in PERFT_FENS {
+            let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+            assert_eq!(board.check_mailbox(), Ok(()));
+            mailbox_walk(&mut board, 3);
+        }
+    }
+
+    #[test]
+    fn test_piece_at(){
+        let board = BitBoard::get_starting_board();
+        assert!(board.piece_at(4) == Some((Player::White, crate::bit_board::PieceType::King)));
+        assert!(board.piece_at(59) == Some((Player::Black, crate::bit_board::PieceType::Queen)));
+        assert!(board.piece_at(28).is_none());
+
+        let mut broken = board.clone();
+        broken.mailbox[28] = broken.mailbox[12];
+        assert!(broken.check_mailbox().is_err());
+    }
+
+    #[test]
+    fn test_slider_tables_match_ray_attacks(){
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        use crate::magic::{bishop_attacks, rook_attacks};
+        use crate::move_generator::{diagonal_ray_attacks, straight_ray_attacks};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for index in 0..64 {
+            for _ in 0..200 {
+                // sparse and dense boards both
+                let occupied = if rng.random::<bool>() {rng.random::<u64>() & rng.random::<u64>()} else {rng.random::<u64>()};
+                assert_eq!(rook_attacks(index, occupied), straight_ray_attacks(index, occupied), "rook on {}", index);
+                assert_eq!(bishop_attacks(index, occupied), diagonal_ray_attacks(index, occupied), "bishop on {}", index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_magic_indexes_without_collisions(){
+        use rand::{rngs::StdRng, SeedableRng};
+        use crate::magic::{bishop_mask, find_magic, subsets};
+        use crate::move_generator::diagonal_ray_attacks;
+
+        let mut rng = StdRng::seed_from_u64(11);
+        for index in [0, 27, 63] {
+            let mask = bishop_mask(index);
+            let occupancies = subsets(mask);
+            let attacks : Vec<u64> = occupancies.iter().map(|occupied| diagonal_ray_attacks(index, *occupied)).collect();
+            let magic = find_magic(mask, &occupancies, &attacks, &mut rng);
+
+            let mut slots = vec![0u64 ; occupancies.len()];
+            for (occupied, attack) in occupancies.iter().zip(&attacks) {
+                let slot = (occupied.wrapping_mul(magic) >> (64 - mask.count_ones())) as usize;
+                assert!(slots[slot] == 0 || slots[slot] == *attack);
+                slots[slot] = *attack;
+            }
+        }
+    }
+
+    #[test]
+    fn test_between_and_line_tables(){
+        use crate::attack_maps::{BETWEEN, LINE};
+
+        // a1 - h8 diagonal
+        assert_eq!(BETWEEN[0][63], (1u64 << 9) | (1u64 << 18) | (1u64 << 27) | (1u64 << 36) | (1u64 << 45) | (1u64 << 54));
+        assert_eq!(LINE[9][27], 0x8040201008040201);
+        // e1 - e8 file, neighbours have nothing between them
+        assert_eq!(BETWEEN[4][60], 0x0010101010101000);
+        assert_eq!(BETWEEN[4][12], 0);
+        assert_eq!(LINE[3][5], RANK_1);
+        // a knight's jump is on no line
+        assert_eq!(BETWEEN[1][18], 0);
+        assert_eq!(LINE[1][18], 0);
+
+        for a in 0..64 {
+            for b in 0..64 {
+                assert_eq!(BETWEEN[a][b], BETWEEN[b][a]);
+                assert_eq!(LINE[a][b], LINE[b][a]);
+                if LINE[a][b] != 0 {
+                    assert_eq!(BETWEEN[a][b] & !LINE[a][b], 0);
+                    assert!(test_bit(LINE[a][b], a) && test_bit(LINE[a][b], b));
+                }
+            }
+        }
+    }
+
+    // the last legal move of every position along the way, so captures, castling and promotions turn up
+    fn play_legal_moves(board : &mut BitBoard , plies : usize) -> Vec<u16> {
+        let mut played = Vec::new();
+        for _ in 0..plies {
+            match board.legal_moves().last().copied() {
+                Some(mov) => {
+                    board.make_move(mov);
+                    played.push(mov);
+                }
+                None => break,
+            }
+        }
+        played
+    }
+
+    #[test]
+    fn test_make_and_unmake_unwind_any_number_of_plies(){
+        for fen in PERFT_FENS {
+            let original = BitBoard::fen_to_bitboard(fen).unwrap();
+            let mut board = original.clone();
+
+            let played = play_legal_moves(&mut board, 12);
+            assert_eq!(board.history.len(), played.len());
+
+            // every position on the way back must match the one we passed through
+            let mut seen = vec![];
+            let mut replay = original.clone();
+            for mov in &played {
+                seen.push((replay.bitboard_to_fen(replay.player == Player::Black), replay.key()));
+                replay.make_move(*mov);
+            }
+            for (mov, expected) in played.iter().rev().zip(seen.iter().rev()) {
+                assert_eq!(board.unmake_move(), Some(*mov));
+                assert_eq!(&(board.bitboard_to_fen(board.player == Player::Black), board.key()), expected);
+                assert_eq!(board.key(), board.zobrist_key());
+            }
+            assert_eq!(board.unmake_move(), None);
+            board.generate_attack_maps(Player::White);
+            board.generate_attack_maps(Player::Black);
+            assert!(board.white_set == original.white_set && board.black_set == original.black_set);
+            assert!(board.player == original.player && board.mailbox == original.mailbox);
+            assert_eq!((board.halfmove_clock, board.fullmove_number), (original.halfmove_clock, original.fullmove_number));
+        }
+    }
+
+    #[test]
+    fn test_make_move_clocks_and_keys(){
+        let mut board = BitBoard::fen_to_bitboard("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let start_key = board.zobrist_key();
+
+        // g1f3 g8f6 f3g1 f6g8 comes back to the start with two full moves played
+        for (src, dest) in [(6, 21), (62, 45), (21, 6), (45, 62)] {
+            board.make_move((src | (dest << 6)) as u16);
+        }
+        assert!(board.player == Player::White);
+        assert_eq!(board.halfmove_clock, 4);
+        assert_eq!(board.fullmove_number, 3);
+        assert_eq!(board.zobrist_key(), start_key);
+        assert_eq!(board.key(), start_key);
+        assert_eq!(board.history[0].key, start_key);
+
+        // the same position reached another way compares equal, whatever the history
+        let replayed = BitBoard::fen_to_bitboard("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 4 3").unwrap();
+        assert!(replayed.history.is_empty());
+        assert!(board == replayed);
+
+        // a double pawn push resets the clock and marks the en passant square in the key
+        board.make_move(12 | (28 << 6) | (8 << 12));
+        assert_eq!(board.halfmove_clock, 0);
+        assert!(board.player == Player::Black);
+        assert_eq!(board.bitboard_to_fen(true), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3");
+        assert_ne!(board.zobrist_key(), start_key);
+
+        board.unmake_move();
+        assert_eq!(board.halfmove_clock, 4);
+        assert_eq!(board.zobrist_key(), start_key);
+    }
+
+    #[test]
+    fn test_validate_positions(){
+        use crate::validation::PositionError;
+
+        let errors = |fen : &str| BitBoard::fen_to_bitboard(fen).unwrap().validate().err().unwrap_or_default();
+
+        for fen in PERFT_FENS {
+            assert_eq!(BitBoard::fen_to_bitboard(fen).unwrap().validate(), Ok(()), "{}", fen);
+        }
+        assert_eq!(BitBoard::get_starting_board().validate(), Ok(()));
+
+        assert_eq!(errors("8/8/8/8/8/8/8/4K3 w - - 0 1"), vec![PositionError::MissingKing(Player::Black)]);
+        assert_eq!(errors("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"), vec![PositionError::TooManyKings(Player::White)]);
+        assert_eq!(errors("4k3/8/8/8/8/8/8/P3K3 w - - 0 1"), vec![PositionError::PawnOnBackRank(0)]);
+        assert_eq!(errors("4k2R/8/8/8/8/8/8/4K3 w - - 0 1"), vec![PositionError::OpponentInCheck]);
+
+        // the pushed pawn must be there, the side to move must be the one to capture
+        assert_eq!(errors("4k3/8/8/8/8/8/8/4K3 b - e3 0 1"), vec![PositionError::InvalidEnPassant(20)]);
+        assert_eq!(errors("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1"), vec![PositionError::InvalidEnPassant(20)]);
+        assert_eq!(errors("4k3/8/8/8/4P3/8/8/4K3 b - e4 0 1"), vec![PositionError::InvalidEnPassant(28)]);
+        assert_eq!(BitBoard::fen_to_bitboard("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap().validate(), Ok(()));
+
+        let mut board = BitBoard::get_starting_board();
+        board.white_set.knights |= 1u64 << 8;
+        assert_eq!(board.validate(), Err(vec![PositionError::OverlappingPieces(8)]));
+        board.white_set.knights ^= 1u64 << 8;
+        board.white_set.occupied |= 1u64 << 20;
+        assert_eq!(board.validate(), Err(vec![PositionError::OccupiedMismatch(Player::White)]));
+
+        // the FEN parser drops rights without a rook, so they can only be set by hand
+        let mut board = BitBoard::fen_to_bitboard("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        board.white_set.castle_rooks = 1u64 << 7;
+        assert_eq!(board.validate(), Err(vec![PositionError::CastlingWithoutRook(Player::White, 7)]));
+
+        let mut board = BitBoard::fen_to_bitboard("4k3/8/8/8/8/8/4K3/7R w - - 0 1").unwrap();
+        board.white_set.castle_rooks = 1u64 << 7;
+        assert_eq!(board.validate(), Err(vec![PositionError::CastlingWithoutKing(Player::White)]));
+
+        assert!(BitBoard::fen_to_valid_bitboard("8/8/8/8/8/8/8/4K3 w - - 0 1").err().unwrap().contains("Black has no king"));
+    }
+
+    #[test]
+    fn test_en_passant_square_from_fen(){
+        let fen = "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3";
+        let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+        assert_eq!(board.white_set.double_push_pawns, 1u64 << 20);
+        assert_eq!(board.bitboard_to_fen(true), fen);
+
+        let capture = board.parse_uci_move("d4e3", false).unwrap();
+        assert_eq!(capture >> 12, 5);
+
+        // a black push keeps the target on the sixth rank in the black set
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+        assert_eq!((board.white_set.double_push_pawns, board.black_set.double_push_pawns), (0, 1u64 << 45));
+        assert_eq!(board.bitboard_to_fen(false), fen);
+        assert_eq!(board.parse_uci_move("e5f6", false).map(|mov| mov >> 12), Some(5));
+
+        // and it is stored the way playing the push stores it
+        let mut played = BitBoard::fen_to_bitboard("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2").unwrap();
+        let push = played.parse_uci_move("f7f5", false).unwrap();
+        played.make_move(push);
+        played.generate_attack_maps(Player::White);
+        played.generate_attack_maps(Player::Black);
+        assert!(played == board);
+        assert_eq!(played.bitboard_to_fen(false), fen);
+    }
+
+    fn play_uci(board : &mut BitBoard , moves : &[&str]) {
+        for text in moves {
+            let mov = board.parse_uci_move(text, false).unwrap();
+            board.make_move(mov);
+        }
+    }
+
+    #[test]
+    fn test_game_status_mate_and_stalemate(){
+        use crate::game_status::GameStatus;
+
+        let mut board = BitBoard::get_starting_board();
+        assert_eq!(board.game_status(), GameStatus::Ongoing);
+        play_uci(&mut board, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(board.game_status(), GameStatus::Checkmate(Player::Black));
+
+        let mut board = BitBoard::fen_to_bitboard("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(board.game_status(), GameStatus::Stalemate);
+
+        // mate on the hundredth ply still counts
+        let mut board = BitBoard::fen_to_bitboard("7k/8/6K1/8/8/8/8/R7 w - - 99 80").unwrap();
+        play_uci(&mut board, &["a1a8"]);
+        assert_eq!(board.game_status(), GameStatus::Checkmate(Player::White));
+    }
+
+    #[test]
+    fn test_game_status_draws(){
+        use crate::game_status::GameStatus;
+
+        let status = |fen : &str| BitBoard::fen_to_bitboard(fen).unwrap().game_status();
+
+        assert_eq!(status("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80"), GameStatus::Ongoing);
+        assert_eq!(status("4k3/8/8/8/8/8/4P3/R3K3 w - - 100 80"), GameStatus::FiftyMoveRule);
+        assert_eq!(status("4k3/8/8/8/8/8/4P3/R3K3 w - - 150 100"), GameStatus::SeventyFiveMoveRule);
+
+        assert_eq!(status("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), GameStatus::InsufficientMaterial);
+        assert_eq!(status("4k3/8/8/8/8/8/8/2N1K3 w - - 0 1"), GameStatus::InsufficientMaterial);
+        assert_eq!(status("2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1"), GameStatus::InsufficientMaterial);
+        assert_eq!(status("3bk3/8/8/8/8/8/8/3BK3 w - - 0 1"), GameStatus::Ongoing);
+        assert_eq!(status("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"), GameStatus::Ongoing);
+        assert_eq!(status("4k3/8/8/8/8/8/8/3PK3 w - - 0 1"), GameStatus::Ongoing);
+
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let mut board = BitBoard::get_starting_board();
+        play_uci(&mut board, &shuffle);
+        assert_eq!(board.repetitions(), 2);
+        assert_eq!(board.game_status(), GameStatus::Ongoing);
+        play_uci(&mut board, &shuffle);
+        assert_eq!(board.game_status(), GameStatus::ThreefoldRepetition);
+        play_uci(&mut board, &shuffle);
+        play_uci(&mut board, &shuffle);
+        assert_eq!(board.repetitions(), 5);
+        assert_eq!(board.game_status(), GameStatus::FivefoldRepetition);
+
+        // a pawn move in between makes the earlier positions unreachable
+        let mut board = BitBoard::get_starting_board();
+        play_uci(&mut board, &shuffle);
+        play_uci(&mut board, &["e2e4", "e7e5"]);
+        play_uci(&mut board, &shuffle);
+        assert_eq!(board.repetitions(), 2);
+    }
+
+    #[test]
+    fn test_attackers_to_matches_piece_attacks(){
+        for fen in PERFT_FENS {
+            let board = BitBoard::fen_to_bitboard(fen).unwrap();
+            let occupied = board.white_set.occupied | board.black_set.occupied;
+
+            for square in 0..64 {
+                let mut expected = 0u64;
+                for index in 0..64 {
+                    let Some((player, piece_type)) = board.piece_at(index) else { continue };
+                    let attacks = match piece_type {
+                        PieceType::Pawn => generate_pawn_attacks(index, &player),
+                        PieceType::Knight => generate_knight_moves(index),
+                        PieceType::Bishop => generate_diagonal_moves(index, occupied),
+                        PieceType::Rook => generate_straight_moves(index, occupied),
+                        PieceType::Queen => generate_diagonal_moves(index, occupied) | generate_straight_moves(index, occupied),
+                        PieceType::King => generate_king_attacks(index),
+                    };
+                    if attacks & (1u64 << square) != 0 {
+                        expected |= 1u64 << index;
+                    }
+                }
+                assert_eq!(board.attackers_to(square, occupied), expected, "{} {}", fen, square);
+            }
+        }
+    }
+
+    #[test]
+    fn test_checkers_and_pins(){
+        let board = BitBoard::fen_to_bitboard("4k3/4r3/8/8/1b6/8/3NR3/4KB1q w - - 0 1").unwrap();
+        assert_eq!(board.checkers(Player::White), 0);
+        assert_eq!(board.pinned(Player::White), squares(&["d2", "e2", "f1"]));
+        assert_eq!(board.pinners(Player::White), squares(&["b4", "e7", "h1"]));
+        // the black rook is pinned in turn by the rook it pins
+        assert_eq!(board.pinned(Player::Black), squares(&["e7"]));
+        assert_eq!(board.pinners(Player::Black), squares(&["e2"]));
+        assert_eq!(board.discovered_check_candidates(Player::White), 0);
+
+        let board = BitBoard::fen_to_bitboard("4k3/8/5N2/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(board.checkers(Player::Black), squares(&["f6", "e1"]));
+        assert!(board.king_in_check(Player::Black));
+        assert!(!board.king_in_check(Player::White));
+
+        let board = BitBoard::fen_to_bitboard("4k3/3P4/8/8/4N3/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(board.checkers(Player::Black), squares(&["d7"]));
+        assert_eq!(board.discovered_check_candidates(Player::White), squares(&["e4"]));
+        assert_eq!(board.pinned(Player::Black), 0);
+
+        // two pieces on the line pin neither
+        let board = BitBoard::fen_to_bitboard("4k3/4r3/8/8/8/4P3/4R3/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.pinned(Player::White), 0);
+        assert_eq!(board.pinners(Player::White), 0);
+    }
+
+    #[test]
+    fn test_gives_check_matches_making_the_move(){
+        let special_cases = [
+            // castling checks with the rook, en passant uncovers a rook, promotion checks along the back rank
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "8/8/8/R2pP2k/8/8/8/4K3 w - d6 0 1",
+            "3k4/P7/8/8/8/8/8/4K3 w - - 0 1",
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+        ];
+
+        fn compare(board : &mut BitBoard , depth : usize) {
+            let turn = board.player;
+            board.generate_attack_maps(turn);
+            board.generate_attack_maps(!turn);
+
+            let info = board.check_info(turn);
+            for mov in board.generate_moves(turn) {
+                let fast = board.gives_check_with(&info, mov);
+                assert_eq!(fast, board.gives_check(mov));
+                board.make_move(mov);
+                if !board.king_in_check(turn) {
+                    assert_eq!(fast, board.king_in_check(!turn), "{} in {}", move_to_uci(mov, true), board.bitboard_to_fen(board.player == Player::Black));
+                    if depth > 1 {
+                        compare(board, depth - 1);
+                    }
+                }
+                board.unmake_move();
+            }
+        }
+
+        for fen in PERFT_FENS.iter().chain(special_cases.iter()) {
+            let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+            compare(&mut board, 3);
+        }
+    }
+
+    #[test]
+    fn test_every_move_value_checked_against_the_generator(){
+        let positions = [
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/8/8/5q2/8/R3K2R w KQkq - 0 1",
+        ];
+
+        for fen in PERFT_FENS.iter().chain(positions.iter()) {
+            let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+            for plies in [0, 2] {
+                play_legal_moves(&mut board, plies);
+                let turn = board.player;
+                board.generate_attack_maps(turn);
+                board.generate_attack_maps(!turn);
+                let generated = board.generate_moves(turn);
 
+                for mov in 0..=u16::MAX {
+                    let expected = generated.contains(&mov);
+                    assert_eq!(board.is_pseudo_legal(mov), expected, "{:#06x} in {}", mov, fen);
+                    if expected {
+                        board.make_move(mov);
+                        let legal = !board.king_in_check(turn);
+                        board.unmake_move();
+                        assert_eq!(board.is_legal(mov), legal, "{:#06x} in {}", mov, fen);
+                    } else {
+                        assert!(!board.is_legal(mov));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_apply_move_rejects_bogus_moves(){
+        let mut board = BitBoard::get_starting_board();
+        let before = board.clone();
+
+        // from an empty square, onto an own piece, a black pawn with white to move, a knight flagged as castling
+        for mov in [20 | (28 << 6), 3 | (4 << 6), 52 | (36 << 6) | (8 << 12), 6 | (7 << 6) | (6 << 12), 12 | (28 << 6) | (9 << 12)] {
+            assert!(board.try_apply_move(mov).is_err());
+            assert!(board == before);
+        }
+
+        // pinned knight
+        let mut board = BitBoard::fen_to_bitboard("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        let knight_jump = 12 | (29 << 6);
+        assert!(board.is_pseudo_legal(knight_jump));
+        assert!(board.try_apply_move(knight_jump).is_err());
+
+        let mut board = BitBoard::get_starting_board();
+        assert_eq!(board.try_apply_move(12 | (28 << 6) | (8 << 12)), Ok(()));
+        assert_eq!(board.player, Player::Black);
+        assert_eq!(board.history.len(), 1);
+    }
+
+    #[test]
+    fn test_generation_stages_split_the_moves(){
+        fn sorted(moves : &[u16]) -> Vec<u16> {
+            let mut moves = moves.to_vec();
+            moves.sort();
+            moves
+        }
+
+        fn legal(board : &mut BitBoard , moves : &[u16]) -> Vec<u16> {
+            let legal_moves = board.legal_moves();
+            let legal = moves.iter().copied().filter(|mov| legal_moves.contains(mov)).collect::<Vec<u16>>();
+            sorted(&legal)
+        }
+
+        fn walk(board : &mut BitBoard , depth : usize , in_check : &mut usize) {
+            let turn = board.player;
+            board.generate_attack_maps(turn);
+            board.generate_attack_maps(!turn);
+
+            let all = board.generate_moves(turn);
+            let captures = board.generate_captures(turn);
+            let quiets = board.generate_quiets(turn);
+
+            let mut union = captures.to_vec();
+            union.extend_from_slice(&quiets);
+            assert_eq!(sorted(&union), sorted(&all), "{}", board.bitboard_to_fen(board.player == Player::Black));
+            assert!(captures.iter().all(|mov| !quiets.contains(mov)));
+
+            let checks : Vec<u16> = quiets.iter().copied().filter(|mov| board.gives_check(*mov)).collect();
+            let quiet_checks = board.generate_quiet_checks(turn);
+            assert_eq!(sorted(&quiet_checks), sorted(&checks), "{}", board.bitboard_to_fen(board.player == Player::Black));
+            assert!(quiet_checks.iter().all(|mov| !captures.contains(mov)));
+
+            if board.king_in_check(turn) {
+                *in_check += 1;
+                let evasions = board.generate_evasions(turn);
+                assert!(evasions.iter().all(|mov| all.contains(mov)));
+                assert_eq!(legal(board, &evasions), legal(board, &all), "{}", board.bitboard_to_fen(board.player == Player::Black));
+            }
+
+            if depth > 1 {
+                for mov in legal(board, &all) {
+                    board.make_move(mov);
+                    walk(board, depth - 1, in_check);
+                    board.unmake_move();
+                }
+            }
+        }
+
+        let mut in_check = 0;
+        for fen in PERFT_FENS {
+            let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+            walk(&mut board, 3, &mut in_check);
+        }
+        assert!(in_check > 0);
+
+        // en passant onto a checking square is a capture, never a quiet check
+        let mut board = BitBoard::fen_to_bitboard("8/4k3/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        let en_passant = board.parse_uci_move("e5d6", false).unwrap();
+        assert!(board.generate_captures(Player::White).contains(&en_passant));
+        assert!(!board.generate_quiet_checks(Player::White).contains(&en_passant));
+        walk(&mut board, 3, &mut in_check);
+    }
+
+    #[test]
+    fn test_perft_options_agree(){
+        use crate::perft::{Perft, PerftHashTable};
+
+        let positions = [(PERFT_FENS[1], 3, 97862), (PERFT_FENS[2], 4, 43238), (PERFT_FENS[4], 3, 62379)];
+
+        let settings = [
+            Perft::new(),
+            Perft { bulk : false, threads : 1, hash : None },
+            Perft { bulk : true, threads : 3, hash : Some(PerftHashTable::new(1)) },
+            Perft { bulk : false, threads : 2, hash : Some(PerftHashTable::new(0)) },
+        ];
+
+        for (fen, depth, expected) in positions {
+            let board = BitBoard::fen_to_bitboard(fen).unwrap();
+            for perft in &settings {
+                assert_eq!(perft.count(&board, depth), expected, "{}", fen);
+            }
+
+            let divide = Perft::new().divide(&board, depth);
+            let mut with_board = board.clone();
+            for (mov, nodes) in divide {
+                with_board.make_move(mov);
+                assert_eq!(Perft::new().count(&with_board, depth - 1), nodes);
+                with_board.unmake_move();
+            }
+        }
+    }
+
+    #[test]
+    fn test_move_generation_agrees_with_pleco(){
+        use crate::pleco_check::{check_random_games, find_divergence};
+
+        // games that end early check fewer positions
+        let checked = check_random_games(20, 150, 1, 1).unwrap_or_else(|e| panic!("{}", e));
+        assert!(checked > 20 * 50);
+
+        for fen in PERFT_FENS {
+            let mut board = BitBoard::fen_to_bitboard(fen).unwrap();
+            assert_eq!(find_divergence(&mut board, 2), Ok(None), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_polyglot_keys(){
+        // the examples of the Polyglot book format
+        let games : [(&[&str], u64) ; 9] = [
+            (&[], 0x463b96181691fc9c),
+            (&["e2e4"], 0x823c9b50fd114196),
+            (&["e2e4", "d7d5"], 0x0756b94461c50fb0),
+            (&["e2e4", "d7d5", "e4e5"], 0x662fafb965db29d4),
+            (&["e2e4", "d7d5", "e4e5", "f7f5"], 0x22a48b5a8e47ff78),
+            (&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"], 0x652a607ca3f242c1),
+            (&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"], 0x00fdd303c946bdd9),
+            (&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"], 0x3c8123ea7b067637),
+            (&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"], 0x5c3f9b829b279560),
+        ];
+        for (moves, key) in games {
+            let mut board = BitBoard::get_starting_board();
+            play_uci(&mut board, moves);
+            assert_eq!(board.polyglot_key(), key, "{:?}", moves);
+        }
+    }
+
+    #[test]
+    fn test_polyglot_book(){
+        use crate::polyglot::PolyglotBook;
+
+        let mut castling = BitBoard::fen_to_bitboard("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let mut start = BitBoard::get_starting_board();
+        let mut after_e4 = start.clone();
+        play_uci(&mut after_e4, &["e2e4"]);
+
+        // e2e4 twice as often as d2d4, a zero weight move, a move that is not legal and a reply to e2e4
+        let path = write_polyglot_book("polyglot", vec![
+            (start.polyglot_key(), polyglot_move(12, 28), 20),
+            (start.polyglot_key(), polyglot_move(11, 27), 10),
+            (start.polyglot_key(), polyglot_move(6, 21), 0),
+            (start.polyglot_key(), polyglot_move(12, 36), 50),
+            (castling.polyglot_key(), polyglot_move(4, 7), 1),
+            (after_e4.polyglot_key(), polyglot_move(52, 36), 5),
+        ]);
+
+        let mut book = PolyglotBook::open(path.to_str().unwrap()).unwrap();
+        let moves = book.moves(&mut start).unwrap();
+        let uci : Vec<String> = moves.iter().map(|(mov, _)| move_to_uci(*mov, false)).collect();
+        assert_eq!(uci.len(), 3);
+        assert!(uci.contains(&"e2e4".to_string()) && uci.contains(&"d2d4".to_string()) && uci.contains(&"g1f3".to_string()));
+
+        let e2e4 = start.parse_uci_move("e2e4", false).unwrap();
+        assert_eq!(book.pick(&mut start), Ok(Some(e2e4)));
+
+        // e1h1 is castling short
+        assert_eq!(book.pick(&mut castling), Ok(Some(castling.parse_uci_move("e1g1", false).unwrap())));
+        assert_eq!(book.pick(&mut BitBoard::fen_to_bitboard("8/8/8/8/8/8/8/K6k w - - 0 1").unwrap()), Ok(None));
+
+        book.random = true;
+        book.seed(7);
+        let picks : Vec<u16> = (0..300).map(|_| book.pick(&mut start).unwrap().unwrap()).collect();
+        let e2e4_picks = picks.iter().filter(|mov| **mov == e2e4).count();
+        assert!(picks.iter().all(|mov| move_to_uci(*mov, false) != "g1f3"));
+        assert!((150..250).contains(&e2e4_picks), "{}", e2e4_picks);
+
+        // a depth of n plays the first n plies from the book
+        book.random = false;
+        let e7e5 = after_e4.parse_uci_move("e7e5", false).unwrap();
+        book.max_depth = 0;
+        assert_eq!(book.pick(&mut start), Ok(None));
+        book.max_depth = 1;
+        assert_eq!(book.pick(&mut start), Ok(Some(e2e4)));
+        assert_eq!(book.pick(&mut after_e4), Ok(None));
+        book.max_depth = 2;
+        assert_eq!(book.pick(&mut after_e4), Ok(Some(e7e5)));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    fn polyglot_move(from : usize , to : usize) -> u16 {
+        (from << 6 | to) as u16
+    }
+
+    // a book file in the temp directory holding `entries` as (key, move, weight)
+    fn write_polyglot_book(name : &str , mut entries : Vec<(u64, u16, u16)>) -> std::path::PathBuf {
+        use std::io::Write;
+
+        entries.sort_by_key(|(key, _, _)| *key);
+        let path = std::env::temp_dir().join(format!("{}-{}.bin", name, std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        for (key, mov, weight) in &entries {
+            file.write_all(&key.to_be_bytes()).unwrap();
+            file.write_all(&mov.to_be_bytes()).unwrap();
+            file.write_all(&weight.to_be_bytes()).unwrap();
+            file.write_all(&0u32.to_be_bytes()).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_run_engine_plays_from_the_book(){
+        use crate::game_status::GameStatus;
+        use crate::polyglot::PolyglotBook;
+
+        // the fool's mate, one book move per position, so the game ends without reading any input
+        let moves = [(13, 21), (52, 36), (14, 30), (59, 31)];
+        let mut board = BitBoard::get_starting_board();
+        let mut entries = vec![];
+        for (from, to) in moves {
+            entries.push((board.polyglot_key(), polyglot_move(from, to), 1));
+            let mov = board.parse_uci_move(&move_to_uci((from | to << 6) as u16, false), false).unwrap();
+            board.make_move(mov);
+        }
+        let path = write_polyglot_book("fools-mate", entries);
+
+        let mut book = PolyglotBook::open(path.to_str().unwrap()).unwrap();
+        let mut board = BitBoard::get_starting_board();
+        board.run_engine(Player::White, Some(&mut book));
+        assert_eq!(board.history.len(), 4);
+        assert_eq!(board.game_status(), GameStatus::Checkmate(Player::Black));
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
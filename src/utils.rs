@@ -30,6 +30,12 @@ pub fn read_move_components() -> Result<(usize, usize, usize), String> {
     Ok((numbers[0], numbers[1], numbers[2]))
 }
 
+// the value following a command line flag, parsed into whatever the flag sets
+pub fn parse_value<T : std::str::FromStr>(name : &str , value : Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("Missing value for {}", name))?;
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", name, value))
+}
+
 
 pub fn print_bitset(set : &u64){
 
@@ -39,9 +45,9 @@ pub fn print_bitset(set : &u64){
             let bit  = if set & (1u64 << sum ) != 0 {1} else {0};
             print!("{} " , bit );
         } 
-        println!("");
+        println!();
     }
-    println!("");
+    println!();
 }
 
 
@@ -73,3 +79,77 @@ pub fn get_msb(bitset :  u64) -> usize {
     bitset.leading_zeros() as usize
 }
 
+
+
+pub const FILE_A : u64 = 0x0101010101010101;
+pub const FILE_H : u64 = FILE_A << 7;
+pub const RANK_1 : u64 = 0xFF;
+pub const RANK_8 : u64 = RANK_1 << 56;
+
+pub fn file_mask(file : usize) -> u64 {
+    FILE_A << file
+}
+
+pub fn north_fill(mut bitset : u64) -> u64 {
+    bitset |= bitset << 8;
+    bitset |= bitset << 16;
+    bitset |= bitset << 32;
+    bitset
+}
+
+pub fn south_fill(mut bitset : u64) -> u64 {
+    bitset |= bitset >> 8;
+    bitset |= bitset >> 16;
+    bitset |= bitset >> 32;
+    bitset
+}
+
+pub fn file_fill(bitset : u64) -> u64 {
+    north_fill(bitset) | south_fill(bitset)
+}
+
+pub fn shift_east(bitset : u64) -> u64 {
+    (bitset << 1) & !FILE_A
+}
+
+pub fn shift_west(bitset : u64) -> u64 {
+    (bitset >> 1) & !FILE_H
+}
+
+pub const DARK_SQUARES : u64 = 0xAA55AA55AA55AA55;
+pub const LIGHT_SQUARES : u64 = !DARK_SQUARES;
+
+// rank 1 becomes rank 8 and so on
+pub fn flip_vertical(bitset : u64) -> u64 {
+    bitset.swap_bytes()
+}
+
+// the a-file becomes the h-file and so on
+pub fn flip_horizontal(bitset : u64) -> u64 {
+    bitset.reverse_bits().swap_bytes()
+}
+
+pub fn square_name(index : usize) -> String {
+    format!("{}{}", (b'a' + (index % 8) as u8) as char, index / 8 + 1)
+}
+
+// long algebraic notation; castling is written as the king taking its rook in Chess960
+// and as the king's two-square step otherwise
+pub fn move_to_uci(mov : u16 , chess960 : bool) -> String {
+    let src = mov as usize & 0x3F;
+    let mut dest = (mov as usize >> 6) & 0x3F;
+    let special = mov >> 12;
+
+    if (special == 6 || special == 7) && !chess960 {
+        dest = (src & 56) + if special == 6 { 6 } else { 2 };
+    }
+
+    let promotion = match special {
+        1 => "n",
+        2 => "b",
+        3 => "r",
+        4 => "q",
+        _ => "",
+    };
+    format!("{}{}{}", square_name(src), square_name(dest), promotion)
+}
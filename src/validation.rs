@@ -0,0 +1,132 @@
+use std::fmt;
+
+use crate::{bit_board::BitBoard, piece_set::PieceSet, player::Player,
+utils::{flip_bit, get_lsb, square_name, RANK_1, RANK_8}};
+
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PositionError {
+    MissingKing(Player),
+    TooManyKings(Player),
+    PawnOnBackRank(usize),
+    // two piece bitboards claim the same square
+    OverlappingPieces(usize),
+    // `occupied` is not the union of the piece bitboards
+    OccupiedMismatch(Player),
+    // a castling right whose rook is gone, or whose king is not on the home rank
+    CastlingWithoutRook(Player, usize),
+    CastlingWithoutKing(Player),
+    OpponentInCheck,
+    InvalidEnPassant(usize),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionError::MissingKing(player) => write!(f, "{:?} has no king", player),
+            PositionError::TooManyKings(player) => write!(f, "{:?} has more than one king", player),
+            PositionError::PawnOnBackRank(index) => write!(f, "Pawn on the back rank at {}", square_name(*index)),
+            PositionError::OverlappingPieces(index) => write!(f, "More than one piece on {}", square_name(*index)),
+            PositionError::OccupiedMismatch(player) => write!(f, "{:?} occupancy does not match its pieces", player),
+            PositionError::CastlingWithoutRook(player, index) => write!(f, "{:?} may castle with a rook on {} that is not there", player, square_name(*index)),
+            PositionError::CastlingWithoutKing(player) => write!(f, "{:?} may castle but its king is not on the home rank", player),
+            PositionError::OpponentInCheck => write!(f, "The side not to move is in check"),
+            PositionError::InvalidEnPassant(index) => write!(f, "Impossible en passant square {}", square_name(*index)),
+        }
+    }
+}
+
+
+fn piece_bitboards(pieces : &PieceSet) -> [u64 ; 6] {
+    [pieces.pawns, pieces.knights, pieces.bishops, pieces.rooks, pieces.queens, pieces.kings]
+}
+
+fn for_each_square(mut squares : u64 , mut f : impl FnMut(usize)) {
+    loop {
+        let index = get_lsb(squares);
+        if index == 64 {
+            break;
+        }
+        f(index);
+        flip_bit(&mut squares, index);
+    }
+}
+
+impl BitBoard {
+    // every problem that would make the position unplayable or the engine misbehave
+    pub fn validate(&self) -> Result<(), Vec<PositionError>> {
+        let mut errors = Vec::new();
+
+        let mut seen = 0u64;
+        let mut overlapping = 0u64;
+        for (player, pieces, home_rank) in [(Player::White, &self.white_set, RANK_1), (Player::Black, &self.black_set, RANK_8)] {
+            match pieces.kings.count_ones() {
+                0 => errors.push(PositionError::MissingKing(player)),
+                1 => {}
+                _ => errors.push(PositionError::TooManyKings(player)),
+            }
+
+            let mut union = 0u64;
+            for bitboard in piece_bitboards(pieces) {
+                overlapping |= seen & bitboard;
+                seen |= bitboard;
+                union |= bitboard;
+            }
+            if union != pieces.occupied {
+                errors.push(PositionError::OccupiedMismatch(player));
+            }
+
+            if pieces.castle_rooks != 0 && pieces.kings & home_rank == 0 {
+                errors.push(PositionError::CastlingWithoutKing(player));
+            }
+            for_each_square(pieces.castle_rooks & !(pieces.rooks & home_rank), |index| {
+                errors.push(PositionError::CastlingWithoutRook(player, index));
+            });
+        }
+
+        for_each_square(overlapping, |index| errors.push(PositionError::OverlappingPieces(index)));
+        for_each_square((self.white_set.pawns | self.black_set.pawns) & (RANK_1 | RANK_8), |index| {
+            errors.push(PositionError::PawnOnBackRank(index));
+        });
+
+        for_each_square(self.white_set.double_push_pawns | self.black_set.double_push_pawns, |index| {
+            if !self.en_passant_possible(index) {
+                errors.push(PositionError::InvalidEnPassant(index));
+            }
+        });
+
+        // with a king missing there is nothing sensible to be in check
+        let kings_ok = self.white_set.kings.count_ones() == 1 && self.black_set.kings.count_ones() == 1;
+        if kings_ok && self.king_in_check(!self.player) {
+            errors.push(PositionError::OpponentInCheck);
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    // `target` is the square a pawn of the side not to move just skipped over
+    fn en_passant_possible(&self , target : usize) -> bool {
+        let (pusher, pusher_set, origin, pawn) = match target / 8 {
+            2 => (Player::White, &self.white_set, target - 8, target + 8),
+            5 => (Player::Black, &self.black_set, target + 8, target - 8),
+            _ => return false,
+        };
+        let occupied = self.white_set.occupied | self.black_set.occupied;
+
+        pusher != self.player
+            && (self.white_set.double_push_pawns | self.black_set.double_push_pawns).count_ones() == 1
+            && pusher_set.double_push_pawns & (1u64 << target) != 0
+            && pusher_set.pawns & (1u64 << pawn) != 0
+            && occupied & (1u64 << target | 1u64 << origin) == 0
+    }
+
+    // `fen_to_bitboard` followed by `validate`, for positions that come from users
+    pub fn fen_to_valid_bitboard(fen : &str) -> Result<BitBoard, String> {
+        let board = BitBoard::fen_to_bitboard(fen)?;
+        board.validate().map_err(|errors| {
+            let reasons : Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+            format!("Invalid position \"{}\": {}", fen, reasons.join("; "))
+        })?;
+        Ok(board)
+    }
+}
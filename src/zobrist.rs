@@ -0,0 +1,194 @@
+use lazy_static::lazy_static;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{attack_maps::PAWN_CAPTURES, bit_board::{BitBoard, MoveResult, PieceType}, engine::castle_destinations, player::Player,
+utils::{flip_bit, get_lsb}};
+
+const CASTLE_KING : u16 = 6;
+
+// fixed seed so keys (and anything stored under them) are stable between runs
+const ZOBRIST_SEED : u64 = 0x9E37_79B9_7F4A_7C15;
+
+lazy_static! {
+    pub static ref PIECE_KEYS: [[[u64; 64]; 6]; 2] = initialize_piece_keys();
+    // indexed by the square of a rook that may still castle
+    pub static ref CASTLE_KEYS: [u64; 64] = random_keys(ZOBRIST_SEED ^ 1);
+    // indexed by the file of the en passant square
+    pub static ref EN_PASSANT_KEYS: [u64; 8] = random_keys(ZOBRIST_SEED ^ 2);
+    pub static ref BLACK_TO_MOVE_KEY: u64 = random_keys::<1>(ZOBRIST_SEED ^ 3)[0];
+}
+
+
+fn initialize_piece_keys() -> [[[u64; 64]; 6]; 2] {
+    let mut rng = StdRng::seed_from_u64(ZOBRIST_SEED);
+    let mut keys = [[[0u64 ; 64] ; 6] ; 2];
+
+    for player_keys in keys.iter_mut() {
+        for piece_keys in player_keys.iter_mut() {
+            for key in piece_keys.iter_mut() {
+                *key = rng.random();
+            }
+        }
+    }
+
+    keys
+}
+
+
+fn random_keys<const N : usize>(seed : u64) -> [u64; N] {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut keys = [0u64 ; N];
+    for key in keys.iter_mut() {
+        *key = rng.random();
+    }
+    keys
+}
+
+
+fn hash_pieces(mut pieces : u64 , player : Player , piece_type : PieceType) -> u64 {
+    let mut key = 0;
+    loop {
+        let index = get_lsb(pieces);
+        if index == 64 {
+            break;
+        }
+        key ^= PIECE_KEYS[player as usize][piece_type as usize][index];
+        flip_bit(&mut pieces, index);
+    }
+    key
+}
+
+
+// calls `f` with every (player, piece type, square) that `apply_move` empties or fills when playing `mov`;
+// the same squares change back when it is taken back
+fn for_each_changed_piece<F>(turn : Player , mov : u16 , mov_result : &MoveResult , mut f : F)
+where
+    F : FnMut(Player, PieceType, usize)
+{
+    let src = mov as usize & 0x3F;
+    let dest = (mov as usize >> 6) & 0x3F;
+
+    match mov_result {
+        MoveResult::NormalMove(src_piece_type, dest_piece_type, ..) => {
+            f(turn, *src_piece_type, src);
+            f(turn, *src_piece_type, dest);
+            if let Some(captured) = dest_piece_type {
+                f(!turn, *captured, dest);
+            }
+        }
+        MoveResult::Promotions(promoted, dest_piece_type, ..) => {
+            f(turn, PieceType::Pawn, src);
+            f(turn, *promoted, dest);
+            if let Some(captured) = dest_piece_type {
+                f(!turn, *captured, dest);
+            }
+        }
+        MoveResult::Enpassant(..) => {
+            f(turn, PieceType::Pawn, src);
+            f(turn, PieceType::Pawn, dest);
+            f(!turn, PieceType::Pawn, (src & 56) | (dest & 7));
+        }
+        MoveResult::DoublePawnPush(..) => {
+            f(turn, PieceType::Pawn, src);
+            f(turn, PieceType::Pawn, dest);
+        }
+        MoveResult::Castle(..) => {
+            let (king_dest, rook_dest) = castle_destinations(src, mov >> 12 == CASTLE_KING);
+            f(turn, PieceType::King, src);
+            f(turn, PieceType::King, king_dest);
+            f(turn, PieceType::Rook, dest);
+            f(turn, PieceType::Rook, rook_dest);
+        }
+    }
+}
+
+// what `mov` changes in `piece_hash`, undone by applying it a second time
+pub fn piece_key_change(turn : Player , mov : u16 , mov_result : &MoveResult) -> u64 {
+    let mut change = 0;
+    for_each_changed_piece(turn, mov, mov_result, |player, piece_type, index| {
+        change ^= PIECE_KEYS[player as usize][piece_type as usize][index];
+    });
+    change
+}
+
+
+impl BitBoard {
+    // brings the incremental keys along with `mov`, called once it is applied or before it is taken back
+    pub fn update_keys(&mut self , turn : Player , mov : u16 , mov_result : &MoveResult) {
+        let (mut piece_hash, mut pawn_hash) = (self.piece_hash, self.pawn_hash);
+        for_each_changed_piece(turn, mov, mov_result, |player, piece_type, index| {
+            let key = PIECE_KEYS[player as usize][piece_type as usize][index];
+            piece_hash ^= key;
+            if piece_type == PieceType::Pawn {
+                pawn_hash ^= key;
+            }
+        });
+        self.piece_hash = piece_hash;
+        self.pawn_hash = pawn_hash;
+    }
+
+    // recomputes the incremental keys, for boards whose bitboards were edited by hand
+    pub fn refresh_keys(&mut self) {
+        self.piece_hash = self.piece_key();
+        self.pawn_hash = self.pawn_key();
+    }
+
+    // zobrist key over every piece, computed from scratch; `piece_hash` holds the same key
+    pub fn piece_key(&self) -> u64 {
+        let mut key = 0;
+        for (player, pieces) in [(Player::White, &self.white_set), (Player::Black, &self.black_set)] {
+            key ^= hash_pieces(pieces.pawns, player, PieceType::Pawn)
+                ^ hash_pieces(pieces.knights, player, PieceType::Knight)
+                ^ hash_pieces(pieces.bishops, player, PieceType::Bishop)
+                ^ hash_pieces(pieces.rooks, player, PieceType::Rook)
+                ^ hash_pieces(pieces.queens, player, PieceType::Queen)
+                ^ hash_pieces(pieces.kings, player, PieceType::King);
+        }
+        key
+    }
+
+    // zobrist key over the pawns only, computed from scratch; `pawn_hash` holds the same key
+    pub fn pawn_key(&self) -> u64 {
+        hash_pieces(self.white_set.pawns, Player::White, PieceType::Pawn)
+            ^ hash_pieces(self.black_set.pawns, Player::Black, PieceType::Pawn)
+    }
+
+    // zobrist key over the whole position: pieces, side to move, castling rights and en passant;
+    // `piece_hash` carries the pieces from move to move, the rest is cheap enough to add on every call
+    pub fn key(&self) -> u64 {
+        self.piece_hash ^ self.state_key()
+    }
+
+    // the same key computed from scratch, to check `key` against
+    pub fn zobrist_key(&self) -> u64 {
+        self.piece_key() ^ self.state_key()
+    }
+
+    // side to move, castling rights and en passant
+    fn state_key(&self) -> u64 {
+        let mut key = 0;
+        for (player, pieces) in [(Player::White, &self.white_set), (Player::Black, &self.black_set)] {
+            let mut castle_rooks = pieces.castle_rooks;
+            loop {
+                let index = get_lsb(castle_rooks);
+                if index == 64 {
+                    break;
+                }
+                key ^= CASTLE_KEYS[index];
+                flip_bit(&mut castle_rooks, index);
+            }
+
+            // only when a pawn could take en passant, otherwise the position repeats one without the push
+            let capturers = if player == Player::White { self.black_set.pawns } else { self.white_set.pawns };
+            let target = get_lsb(pieces.double_push_pawns);
+            if target != 64 && PAWN_CAPTURES[player as usize][target] & capturers != 0 {
+                key ^= EN_PASSANT_KEYS[target % 8];
+            }
+        }
+
+        if self.player == Player::Black {
+            key ^= *BLACK_TO_MOVE_KEY;
+        }
+        key
+    }
+}